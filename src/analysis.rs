@@ -0,0 +1,208 @@
+use crate::parser::{Expression, Program, Statement};
+use crate::unparse::unparse_expression;
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiagnosticKind {
+    UnusedExpression,
+    UndefinedVariable,
+    UseBeforeDeclaration,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub kind: DiagnosticKind,
+    pub message: String,
+}
+
+pub fn lint(program: &Program) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    lint_statements(&program.statements, &mut diagnostics);
+    diagnostics
+}
+
+fn lint_statements(statements: &[Statement], diagnostics: &mut Vec<Diagnostic>) {
+    for statement in statements {
+        match statement {
+            Statement::Expression(expr) if !has_side_effects(expr) => {
+                diagnostics.push(Diagnostic {
+                    kind: DiagnosticKind::UnusedExpression,
+                    message: format!("result of '{}' is never used", unparse_expression(expr)),
+                });
+            }
+            Statement::Switch { cases, default, .. } => {
+                for (_, body) in cases {
+                    lint_statements(body, diagnostics);
+                }
+                if let Some(body) = default {
+                    lint_statements(body, diagnostics);
+                }
+            }
+            Statement::If {
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                lint_statements(then_branch, diagnostics);
+                if let Some(body) = else_branch {
+                    lint_statements(body, diagnostics);
+                }
+            }
+            Statement::While { body, .. } => lint_statements(body, diagnostics),
+            _ => {}
+        }
+    }
+}
+
+fn has_side_effects(expression: &Expression) -> bool {
+    match expression {
+        Expression::Literal(_) | Expression::Identifier(_) => false,
+        Expression::FunctionCall { .. } | Expression::Assignment { .. } => true,
+        Expression::Binary { left, right, .. } => has_side_effects(left) || has_side_effects(right),
+    }
+}
+
+pub fn check(program: &Program) -> Vec<Diagnostic> {
+    check_with(program, &[])
+}
+
+/// Like `check`, but treats `known` as already declared, for names the host
+/// seeds before running the script (`Interpreter::with_variables` and
+/// friends).
+pub fn check_with<'p>(program: &'p Program, known: &[&'p str]) -> Vec<Diagnostic> {
+    let mut all_declared = HashSet::new();
+    collect_declarations(&program.statements, &mut all_declared);
+
+    let mut checker = Checker {
+        all_declared,
+        declared: known.iter().copied().collect(),
+        diagnostics: Vec::new(),
+    };
+    checker.check_statements(&program.statements);
+    checker.diagnostics
+}
+
+fn collect_declarations<'p>(statements: &'p [Statement], names: &mut HashSet<&'p str>) {
+    for statement in statements {
+        match statement {
+            Statement::VarDeclaration { name, .. } => {
+                names.insert(name);
+            }
+            Statement::Switch { cases, default, .. } => {
+                for (_, body) in cases {
+                    collect_declarations(body, names);
+                }
+                if let Some(body) = default {
+                    collect_declarations(body, names);
+                }
+            }
+            Statement::If {
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                collect_declarations(then_branch, names);
+                if let Some(body) = else_branch {
+                    collect_declarations(body, names);
+                }
+            }
+            Statement::While { body, .. } => collect_declarations(body, names),
+            Statement::TupleAssignment { .. } | Statement::Expression(_) => {}
+        }
+    }
+}
+
+struct Checker<'p> {
+    all_declared: HashSet<&'p str>,
+    declared: HashSet<&'p str>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'p> Checker<'p> {
+    fn check_statements(&mut self, statements: &'p [Statement]) {
+        for statement in statements {
+            match statement {
+                Statement::VarDeclaration { name, value } => {
+                    self.check_expression(value);
+                    self.declared.insert(name);
+                }
+                Statement::TupleAssignment { names, values } => {
+                    for value in values {
+                        self.check_expression(value);
+                    }
+                    for name in names {
+                        self.check_use(name);
+                    }
+                }
+                Statement::Switch {
+                    subject,
+                    cases,
+                    default,
+                } => {
+                    self.check_expression(subject);
+                    for (value, body) in cases {
+                        self.check_expression(value);
+                        self.check_statements(body);
+                    }
+                    if let Some(body) = default {
+                        self.check_statements(body);
+                    }
+                }
+                Statement::If {
+                    condition,
+                    then_branch,
+                    else_branch,
+                } => {
+                    self.check_expression(condition);
+                    self.check_statements(then_branch);
+                    if let Some(body) = else_branch {
+                        self.check_statements(body);
+                    }
+                }
+                Statement::While { condition, body } => {
+                    self.check_expression(condition);
+                    self.check_statements(body);
+                }
+                Statement::Expression(expr) => self.check_expression(expr),
+            }
+        }
+    }
+
+    fn check_expression(&mut self, expression: &'p Expression) {
+        match expression {
+            Expression::Literal(_) => {}
+            Expression::Identifier(name) => self.check_use(name),
+            Expression::FunctionCall { args, .. } => {
+                for arg in args {
+                    self.check_expression(arg);
+                }
+            }
+            Expression::Binary { left, right, .. } => {
+                self.check_expression(left);
+                self.check_expression(right);
+            }
+            Expression::Assignment { name, value } => {
+                self.check_expression(value);
+                self.check_use(name);
+            }
+        }
+    }
+
+    fn check_use(&mut self, name: &str) {
+        if self.declared.contains(name) {
+            return;
+        }
+
+        self.diagnostics.push(if self.all_declared.contains(name) {
+            Diagnostic {
+                kind: DiagnosticKind::UseBeforeDeclaration,
+                message: format!("'{name}' is used before it is declared"),
+            }
+        } else {
+            Diagnostic {
+                kind: DiagnosticKind::UndefinedVariable,
+                message: format!("'{name}' is never declared"),
+            }
+        });
+    }
+}
@@ -1,27 +1,209 @@
-use crate::lexer::Literal;
-use crate::parser::{BinaryOp, Expression, Program, Statement};
-use std::collections::HashMap;
+use crate::lexer::{Lexer, Literal, parse_number_str};
+use crate::parser::{BinaryOp, Expression, ParseError, Parser, Program, Statement};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+use std::iter;
+#[cfg(feature = "fs")]
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Value {
     String(String),
     Number(f64),
     Boolean(bool),
+    Bytes(Vec<u8>),
     Null,
 }
 
+/// Every current variant can be used as a hash key. Numbers hash by bit
+/// pattern with `-0.0` folded into `0.0`, matching `==`; `NaN` never equals
+/// itself, so a `NaN` key can be inserted but never looked up again. All `NaN`
+/// payloads hash alike.
+impl Eq for Value {}
+
+impl std::hash::Hash for Value {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Value::String(s) => s.hash(state),
+            Value::Number(n) => canonical_bits(*n).hash(state),
+            Value::Boolean(b) => b.hash(state),
+            Value::Bytes(bytes) => bytes.hash(state),
+            Value::Null => {}
+        }
+    }
+}
+
+fn canonical_bits(n: f64) -> u64 {
+    if n == 0.0 {
+        0.0f64.to_bits()
+    } else if n.is_nan() {
+        f64::NAN.to_bits()
+    } else {
+        n.to_bits()
+    }
+}
+
+pub const MAX_INDEX: usize = 1 << 24;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValueKind {
+    String,
+    Number,
+    Boolean,
+    Bytes,
+    Null,
+}
+
+/// Values only order against values of the same variant; comparing across
+/// variants yields `None`, as does any comparison involving `NaN`.
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        match (self, other) {
+            (Value::String(l), Value::String(r)) => l.partial_cmp(r),
+            (Value::Number(l), Value::Number(r)) => l.partial_cmp(r),
+            (Value::Boolean(l), Value::Boolean(r)) => l.partial_cmp(r),
+            (Value::Bytes(l), Value::Bytes(r)) => l.partial_cmp(r),
+            (Value::Null, Value::Null) => Some(std::cmp::Ordering::Equal),
+            _ => None,
+        }
+    }
+}
+
+impl Value {
+    pub fn kind(&self) -> ValueKind {
+        match self {
+            Value::String(_) => ValueKind::String,
+            Value::Number(_) => ValueKind::Number,
+            Value::Boolean(_) => ValueKind::Boolean,
+            Value::Bytes(_) => ValueKind::Bytes,
+            Value::Null => ValueKind::Null,
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self.kind() {
+            ValueKind::String => "string",
+            ValueKind::Number => "number",
+            ValueKind::Boolean => "boolean",
+            ValueKind::Bytes => "bytes",
+            ValueKind::Null => "null",
+        }
+    }
+
+    pub fn as_number(&self) -> Result<f64, RuntimeError> {
+        match self {
+            Value::Number(n) => Ok(*n),
+            other => Err(Self::expected("number", other)),
+        }
+    }
+
+    pub fn as_string(&self) -> Result<&str, RuntimeError> {
+        match self {
+            Value::String(s) => Ok(s),
+            other => Err(Self::expected("string", other)),
+        }
+    }
+
+    pub fn as_bool(&self) -> Result<bool, RuntimeError> {
+        match self {
+            Value::Boolean(b) => Ok(*b),
+            other => Err(Self::expected("boolean", other)),
+        }
+    }
+
+    pub fn as_bytes(&self) -> Result<&[u8], RuntimeError> {
+        match self {
+            Value::Bytes(bytes) => Ok(bytes),
+            other => Err(Self::expected("bytes", other)),
+        }
+    }
+
+    /// Indices and counts are capped at `MAX_INDEX` so that a huge number
+    /// can't make a built-in such as `pad_left` attempt a giant allocation.
+    pub fn as_index(&self) -> Result<usize, RuntimeError> {
+        match self {
+            Value::Number(n) if *n > MAX_INDEX as f64 => Err(RuntimeError::TypeError(format!(
+                "expected an integer no larger than {MAX_INDEX}, got {n}"
+            ))),
+            Value::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Ok(*n as usize),
+            Value::Number(n) => Err(RuntimeError::TypeError(format!(
+                "expected a non-negative integer, got {n}"
+            ))),
+            other => Err(Self::expected("a non-negative integer", other)),
+        }
+    }
+
+    fn expected(type_name: &str, found: &Value) -> RuntimeError {
+        RuntimeError::TypeError(format!("expected {type_name}, got {}", found.type_name()))
+    }
+
+    /// FNV-1a over a fixed encoding (a tag byte, then little-endian lengths
+    /// and bits or raw bytes) rather than `Hash`, so the result does not
+    /// depend on the Rust version or platform.
+    pub fn digest(&self) -> u64 {
+        let mut hasher = Fnv1a(0xcbf2_9ce4_8422_2325);
+        match self {
+            Value::String(s) => {
+                hasher.write(&[0]);
+                hasher.write(&(s.len() as u64).to_le_bytes());
+                hasher.write(s.as_bytes());
+            }
+            Value::Number(n) => {
+                hasher.write(&[1]);
+                hasher.write(&canonical_bits(*n).to_le_bytes());
+            }
+            Value::Boolean(b) => hasher.write(&[2, u8::from(*b)]),
+            Value::Bytes(bytes) => {
+                hasher.write(&[3]);
+                hasher.write(&(bytes.len() as u64).to_le_bytes());
+                hasher.write(bytes);
+            }
+            Value::Null => hasher.write(&[4]),
+        }
+        hasher.0
+    }
+}
+
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
 impl std::fmt::Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Value::String(s) => write!(f, "{s}"),
             Value::Number(n) => {
-                if n.fract() == 0.0 {
+                if n.is_nan() {
+                    write!(f, "NaN")
+                } else if n.is_infinite() {
+                    write!(f, "{}Infinity", if *n < 0.0 { "-" } else { "" })
+                } else if n.fract() == 0.0 {
                     write!(f, "{0}", *n as i64)
                 } else {
                     write!(f, "{n}")
                 }
             }
             Value::Boolean(b) => write!(f, "{b}"),
+            Value::Bytes(bytes) => {
+                write!(f, "<bytes ")?;
+                for byte in bytes {
+                    write!(f, "{byte:02x}")?;
+                }
+                write!(f, ">")
+            }
             Value::Null => write!(f, "null"),
         }
     }
@@ -32,11 +214,23 @@ pub enum RuntimeError {
     UndefinedVariable(String),
     UndefinedFunction(String),
     TypeError(String),
+    IoError(String),
+    PermissionDenied(String),
     ArityMismatch {
         function: String,
         expected: usize,
         found: usize,
     },
+    StepLimitExceeded(u64),
+    DepthLimitExceeded(usize),
+    SleepLimitExceeded(Duration),
+    ArgumentLimitExceeded(usize),
+    IterationLimitExceeded(u64),
+    Timeout,
+    Overflow,
+    AssertionFailed(String),
+    AssignToConst(String),
+    Parse(ParseError),
 }
 
 impl std::fmt::Display for RuntimeError {
@@ -45,6 +239,8 @@ impl std::fmt::Display for RuntimeError {
             RuntimeError::UndefinedVariable(name) => write!(f, "Undefined variable '{name}'"),
             RuntimeError::UndefinedFunction(name) => write!(f, "Undefined function '{name}'"),
             RuntimeError::TypeError(msg) => write!(f, "Type error: {msg}"),
+            RuntimeError::IoError(msg) => write!(f, "IO error: {msg}"),
+            RuntimeError::PermissionDenied(msg) => write!(f, "Permission denied: {msg}"),
             RuntimeError::ArityMismatch {
                 function,
                 expected,
@@ -53,31 +249,182 @@ impl std::fmt::Display for RuntimeError {
                 f,
                 "Function '{function}' expects {expected} arguments, but {found} were provided"
             ),
+            RuntimeError::StepLimitExceeded(limit) => {
+                write!(f, "Step limit of {limit} exceeded")
+            }
+            RuntimeError::DepthLimitExceeded(limit) => {
+                write!(f, "Expression nesting depth of {limit} exceeded")
+            }
+            RuntimeError::SleepLimitExceeded(limit) => {
+                write!(f, "Sleep limit of {}s exceeded", limit.as_secs_f64())
+            }
+            RuntimeError::ArgumentLimitExceeded(limit) => {
+                write!(f, "Call argument limit of {limit} exceeded")
+            }
+            RuntimeError::IterationLimitExceeded(limit) => {
+                write!(f, "Loop iteration limit of {limit} exceeded")
+            }
+            RuntimeError::Timeout => write!(f, "Execution deadline exceeded"),
+            RuntimeError::Overflow => write!(f, "Arithmetic overflow"),
+            RuntimeError::AssertionFailed(msg) => write!(f, "Assertion failed: {msg}"),
+            RuntimeError::AssignToConst(name) => {
+                write!(f, "Cannot assign to constant '{name}'")
+            }
+            RuntimeError::Parse(error) => write!(f, "Parse error: {error:?}"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum EvalError {
+    Parse(ParseError),
+    Runtime(RuntimeError),
+}
+
+impl std::fmt::Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvalError::Parse(error) => write!(f, "Parse error: {error:?}"),
+            EvalError::Runtime(error) => write!(f, "Runtime error: {error}"),
         }
     }
 }
 
+impl From<ParseError> for EvalError {
+    fn from(error: ParseError) -> Self {
+        EvalError::Parse(error)
+    }
+}
+
+impl From<RuntimeError> for EvalError {
+    fn from(error: RuntimeError) -> Self {
+        EvalError::Runtime(error)
+    }
+}
+
+type DefineCallback = dyn FnMut(&str, &Value);
+
+struct DefineHook(Box<DefineCallback>);
+
+impl std::fmt::Debug for DefineHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("DefineHook")
+    }
+}
+
 #[derive(Debug)]
 pub struct Environment {
     variables: HashMap<String, Value>,
+    constants: HashSet<String>,
+    case_insensitive: bool,
+    on_define: Option<DefineHook>,
 }
 
 impl Environment {
     pub fn new() -> Self {
         Self {
             variables: HashMap::new(),
+            constants: HashSet::new(),
+            case_insensitive: false,
+            on_define: None,
+        }
+    }
+
+    /// Names defined before case-insensitivity is turned on are re-keyed to
+    /// lowercase so they stay reachable; if two names collide, one of them
+    /// wins arbitrarily.
+    pub fn set_case_insensitive(&mut self, enabled: bool) {
+        self.case_insensitive = enabled;
+        if enabled {
+            self.variables = self
+                .variables
+                .drain()
+                .map(|(name, value)| (name.to_lowercase(), value))
+                .collect();
+            self.constants = self
+                .constants
+                .drain()
+                .map(|name| name.to_lowercase())
+                .collect();
+        }
+    }
+
+    fn fork(&self) -> Self {
+        Self {
+            variables: self.variables.clone(),
+            constants: self.constants.clone(),
+            case_insensitive: self.case_insensitive,
+            on_define: None,
         }
     }
+
+    pub fn on_define(&mut self, callback: impl FnMut(&str, &Value) + 'static) {
+        self.on_define = Some(DefineHook(Box::new(callback)));
+    }
+
     pub fn define(&mut self, name: String, value: Value) {
+        let name = self.canonical_name(&name).into_owned();
+        if let Some(hook) = &mut self.on_define {
+            (hook.0)(&name, &value);
+        }
+        self.variables.insert(name, value);
+    }
+
+    pub fn define_constant(&mut self, name: String, value: Value) {
+        let name = self.canonical_name(&name).into_owned();
+        self.constants.insert(name.clone());
         self.variables.insert(name, value);
     }
 
+    pub fn is_constant(&self, name: &str) -> bool {
+        self.constants.contains(self.canonical_name(name).as_ref())
+    }
+
+    pub fn check_assignable(&self, name: &str) -> Result<(), RuntimeError> {
+        if self.is_constant(name) {
+            return Err(RuntimeError::AssignToConst(name.to_string()));
+        }
+        if !self
+            .variables
+            .contains_key(self.canonical_name(name).as_ref())
+        {
+            return Err(RuntimeError::UndefinedVariable(name.to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn assign(&mut self, name: &str, value: Value) -> Result<(), RuntimeError> {
+        if self.is_constant(name) {
+            return Err(RuntimeError::AssignToConst(name.to_string()));
+        }
+
+        let key = self.canonical_name(name);
+        match self.variables.get_mut(key.as_ref()) {
+            Some(slot) => {
+                if let Some(hook) = &mut self.on_define {
+                    (hook.0)(&key, &value);
+                }
+                *slot = value;
+                Ok(())
+            }
+            None => Err(RuntimeError::UndefinedVariable(name.to_string())),
+        }
+    }
+
     pub fn get(&self, name: &str) -> Result<Value, RuntimeError> {
         self.variables
-            .get(name)
+            .get(self.canonical_name(name).as_ref())
             .cloned()
             .ok_or_else(|| RuntimeError::UndefinedVariable(name.to_string()))
     }
+
+    fn canonical_name<'n>(&self, name: &'n str) -> Cow<'n, str> {
+        if self.case_insensitive {
+            Cow::Owned(name.to_lowercase())
+        } else {
+            Cow::Borrowed(name)
+        }
+    }
 }
 
 impl Default for Environment {
@@ -86,161 +433,1413 @@ impl Default for Environment {
     }
 }
 
+#[derive(Clone)]
+struct Output(Rc<RefCell<Box<dyn Write>>>);
+
+impl std::fmt::Debug for Output {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Output")
+    }
+}
+
+/// An in-memory writer for capturing script output; clones share the same
+/// buffer, so one can be handed to `set_output` and the other read back.
+#[derive(Clone, Default)]
+pub struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl SharedBuffer {
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumberFormat {
+    pub decimal_separator: char,
+    pub grouping_separator: Option<char>,
+}
+
+impl NumberFormat {
+    fn format(&self, n: f64) -> String {
+        let text = Value::Number(n).to_string();
+        if !n.is_finite() {
+            return text;
+        }
+
+        let (sign, digits) = match text.strip_prefix('-') {
+            Some(rest) => ("-", rest),
+            None => ("", text.as_str()),
+        };
+        let (whole, fraction) = match digits.split_once('.') {
+            Some((whole, fraction)) => (whole, Some(fraction)),
+            None => (digits, None),
+        };
+
+        let mut out = sign.to_string();
+        for (i, digit) in whole.chars().enumerate() {
+            if let Some(separator) = self.grouping_separator
+                && i > 0
+                && (whole.len() - i).is_multiple_of(3)
+            {
+                out.push(separator);
+            }
+            out.push(digit);
+        }
+        if let Some(fraction) = fraction {
+            out.push(self.decimal_separator);
+            out.push_str(fraction);
+        }
+        out
+    }
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        Self {
+            decimal_separator: '.',
+            grouping_separator: None,
+        }
+    }
+}
+
+pub type NativeFn = fn(Vec<Value>) -> Result<Value, RuntimeError>;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Limits {
+    pub max_steps: Option<u64>,
+    pub max_depth: Option<usize>,
+    pub max_sleep: Option<Duration>,
+    pub max_call_args: Option<usize>,
+    pub max_loop_iterations: Option<u64>,
+}
+
 #[derive(Debug)]
 pub struct Interpreter {
     environment: Environment,
+    output: Output,
+    output_buffered: bool,
+    pending_output: Vec<u8>,
+    natives: HashMap<String, NativeFn>,
+    builtins_enabled: bool,
+    eval_enabled: bool,
+    debug_assertions: bool,
+    limits: Limits,
+    steps: u64,
+    depth: usize,
+    timeout: Option<Duration>,
+    deadline: Option<Instant>,
+    number_epsilon: f64,
+    error_on_infinity: bool,
+    number_format: NumberFormat,
+    allowed_env_vars: HashSet<String>,
+    #[cfg(feature = "fs")]
+    fs_root: Option<PathBuf>,
 }
 
 impl Interpreter {
     pub fn new() -> Self {
         Self {
             environment: Environment::new(),
+            output: Output(Rc::new(RefCell::new(Box::new(io::stdout())))),
+            output_buffered: false,
+            pending_output: Vec::new(),
+            natives: HashMap::new(),
+            builtins_enabled: true,
+            eval_enabled: false,
+            debug_assertions: true,
+            limits: Limits::default(),
+            steps: 0,
+            depth: 0,
+            timeout: None,
+            deadline: None,
+            number_epsilon: 0.0,
+            error_on_infinity: false,
+            number_format: NumberFormat::default(),
+            allowed_env_vars: HashSet::new(),
+            #[cfg(feature = "fs")]
+            fs_root: None,
         }
     }
-    pub fn interpret(&mut self, program: Program) -> Result<(), RuntimeError> {
-        for statement in program.statements {
-            self.execute_statement(statement)?;
+
+    pub fn with_limits(limits: Limits) -> Self {
+        Self {
+            limits,
+            ..Self::new()
         }
-        Ok(())
     }
 
-    fn execute_statement(&mut self, statement: Statement) -> Result<(), RuntimeError> {
-        match statement {
-            Statement::VarDeclaration { name, value } => {
-                let val = self.evaluate_expression(value)?;
-                self.environment.define(name, val);
-                Ok(())
-            }
-            Statement::Expression(expr) => {
-                self.evaluate_expression(expr)?;
-                Ok(())
-            }
+    pub fn with_variables(variables: HashMap<String, Value>) -> Self {
+        let mut interpreter = Self::new();
+        for (name, value) in variables {
+            interpreter.environment.define(name, value);
         }
+        interpreter
     }
 
-    fn evaluate_expression(&mut self, expression: Expression) -> Result<Value, RuntimeError> {
-        match expression {
-            Expression::Literal(literal) => Ok(self.literal_to_value(literal)),
-            Expression::Identifier(name) => self.environment.get(&name),
-            Expression::FunctionCall { name, args } => self.call_function(name, args),
-            Expression::Binary { left, op, right } => {
-                let left_val = self.evaluate_expression(*left)?;
-                let right_val = self.evaluate_expression(*right)?;
-                self.evaluate_binary_op(left_val, op, right_val)
-            }
+    pub fn with_constants(constants: HashMap<String, Value>) -> Self {
+        let mut interpreter = Self::new();
+        for (name, value) in constants {
+            interpreter.define_constant(name, value);
         }
+        interpreter
     }
 
-    fn literal_to_value(&self, literal: Literal) -> Value {
-        match literal {
-            Literal::String(s) => Value::String(s),
-            Literal::Number(n) => Value::Number(n),
-            Literal::Boolean(b) => Value::Boolean(b),
+    /// The fork shares the parent's output writer; call `set_output` on it to
+    /// send its output elsewhere.
+    pub fn fork(&self) -> Self {
+        Self {
+            environment: self.environment.fork(),
+            output: self.output.clone(),
+            output_buffered: self.output_buffered,
+            natives: self.natives.clone(),
+            builtins_enabled: self.builtins_enabled,
+            eval_enabled: self.eval_enabled,
+            debug_assertions: self.debug_assertions,
+            limits: self.limits.clone(),
+            timeout: self.timeout,
+            number_epsilon: self.number_epsilon,
+            error_on_infinity: self.error_on_infinity,
+            number_format: self.number_format.clone(),
+            allowed_env_vars: self.allowed_env_vars.clone(),
+            #[cfg(feature = "fs")]
+            fs_root: self.fs_root.clone(),
+            ..Self::new()
         }
     }
 
-    fn evaluate_binary_op(
-        &self,
-        left: Value,
-        op: BinaryOp,
-        right: Value,
-    ) -> Result<Value, RuntimeError> {
-        match (left, right) {
-            (Value::Number(l), Value::Number(r)) => {
-                let result = match op {
-                    BinaryOp::Add => l + r,
-                    BinaryOp::Subtract => l - r,
-                    BinaryOp::Multiply => l * r,
-                    BinaryOp::Divide => {
-                        if r == 0.0 {
-                            return Err(RuntimeError::TypeError("Division by zero".to_string()));
-                        }
-                        l / r
-                    }
-                };
-                Ok(Value::Number(result))
-            }
-            (Value::String(l), Value::String(r)) if matches!(op, BinaryOp::Add) => {
-                Ok(Value::String(format!("{l}{r}")))
-            }
-            (l, r) => Err(RuntimeError::TypeError(format!(
-                "Cannot apply {:?} to {} and {}",
-                op,
-                value_type_name(&l),
-                value_type_name(&r)
-            ))),
-        }
+    pub fn define_constant(&mut self, name: impl Into<String>, value: Value) {
+        self.environment.define_constant(name.into(), value);
     }
 
-    fn call_function(
-        &mut self,
-        name: String,
-        args: Vec<Expression>,
-    ) -> Result<Value, RuntimeError> {
-        let mut arg_values = Vec::new();
-        for arg in args {
-            arg_values.push(self.evaluate_expression(arg)?);
-        }
+    pub fn register_function(&mut self, name: impl Into<String>, function: NativeFn) {
+        let name = self.environment.canonical_name(&name.into()).into_owned();
+        self.natives.insert(name, function);
+    }
 
-        match name.as_str() {
-            "print" => self.builtin_print(arg_values),
-            "println" => self.builtin_println(arg_values),
-            "typeof" => self.builtin_typeof(arg_values),
-            _ => Err(RuntimeError::UndefinedFunction(name)),
+    pub fn define_module(&mut self, name: &str, functions: HashMap<String, NativeFn>) {
+        for (function, native) in functions {
+            self.register_function(format!("{name}.{function}"), native);
         }
     }
 
-    fn builtin_print(&self, args: Vec<Value>) -> Result<Value, RuntimeError> {
-        for (i, arg) in args.iter().enumerate() {
-            if i > 0 {
-                print!(" ");
-            }
-            print!("{arg}");
-        }
-        Ok(Value::Null)
+    pub fn set_builtins_enabled(&mut self, enabled: bool) {
+        self.builtins_enabled = enabled;
     }
 
-    fn builtin_println(&self, args: Vec<Value>) -> Result<Value, RuntimeError> {
-        self.builtin_print(args)?;
-        println!();
-        Ok(Value::Null)
+    pub fn set_eval_enabled(&mut self, enabled: bool) {
+        self.eval_enabled = enabled;
     }
 
-    fn builtin_typeof(&self, args: Vec<Value>) -> Result<Value, RuntimeError> {
-        if args.len() != 1 {
-            return Err(RuntimeError::ArityMismatch {
-                function: "typeof".to_string(),
-                expected: 1,
-                found: args.len(),
-            });
+    pub fn set_debug_assertions(&mut self, enabled: bool) {
+        self.debug_assertions = enabled;
+    }
+
+    pub fn run_str(source: &str) -> Result<(String, Value), EvalError> {
+        Interpreter::new().run_source(source)
+    }
+
+    /// Like `run_str`, but runs against this interpreter's variables and
+    /// settings. Output is captured for the call and the previous writer is
+    /// restored afterwards.
+    pub fn run_source(&mut self, source: &str) -> Result<(String, Value), EvalError> {
+        let lexer = Lexer::new(source);
+        let program = Parser::new(lexer).parse()?;
+
+        let buffer = SharedBuffer::default();
+        let previous = std::mem::replace(
+            &mut self.output,
+            Output(Rc::new(RefCell::new(Box::new(buffer.clone())))),
+        );
+        let result = self.evaluate(program);
+        self.output = previous;
+
+        Ok((buffer.contents(), result?))
+    }
+
+    pub fn set_output(&mut self, writer: impl Write + 'static) {
+        self.output = Output(Rc::new(RefCell::new(Box::new(writer))));
+    }
+
+    /// Caps the wall-clock time of each `evaluate`/`interpret` call; the
+    /// clock starts when the call begins, not when the timeout is set.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = Some(timeout);
+    }
+
+    pub fn set_number_epsilon(&mut self, epsilon: f64) {
+        self.number_epsilon = epsilon;
+    }
+
+    pub fn set_output_buffered(&mut self, enabled: bool) {
+        self.output_buffered = enabled;
+    }
+
+    pub fn set_number_format(&mut self, format: NumberFormat) {
+        self.number_format = format;
+    }
+
+    pub fn set_error_on_infinity(&mut self, enabled: bool) {
+        self.error_on_infinity = enabled;
+    }
+
+    pub fn set_case_insensitive(&mut self, enabled: bool) {
+        self.environment.set_case_insensitive(enabled);
+        if enabled {
+            self.natives = self
+                .natives
+                .drain()
+                .map(|(name, function)| (name.to_lowercase(), function))
+                .collect();
         }
+    }
 
-        let type_name = match &args[0] {
-            Value::String(_) => "string",
-            Value::Number(_) => "number",
-            Value::Boolean(_) => "boolean",
-            Value::Null => "null",
-        };
+    pub fn on_define(&mut self, callback: impl FnMut(&str, &Value) + 'static) {
+        self.environment.on_define(callback);
+    }
 
-        Ok(Value::String(type_name.to_string()))
+    pub fn allow_env_var(&mut self, name: impl Into<String>) {
+        self.allowed_env_vars.insert(name.into());
     }
 
-    pub fn get_variables(&self) -> &HashMap<String, Value> {
-        &self.environment.variables
+    #[cfg(feature = "fs")]
+    pub fn set_fs_root(&mut self, root: impl Into<PathBuf>) {
+        self.fs_root = Some(root.into());
     }
-}
 
-impl Default for Interpreter {
-    fn default() -> Self {
-        Self::new()
+    pub fn interpret(&mut self, program: Program) -> Result<(), RuntimeError> {
+        self.evaluate(program)?;
+        Ok(())
     }
-}
 
-fn value_type_name(value: &Value) -> &str {
-    match value {
-        Value::String(_) => "string",
-        Value::Number(_) => "number",
-        Value::Boolean(_) => "boolean",
-        Value::Null => "null",
+    pub fn evaluate(&mut self, program: Program) -> Result<Value, RuntimeError> {
+        self.steps = 0;
+        self.depth = 0;
+        self.deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        let result = self.execute_block(&program.statements);
+        let flushed = self.flush_output();
+        let value = result?;
+        flushed?;
+        Ok(value)
     }
+
+    fn execute_statement(&mut self, statement: &Statement) -> Result<Value, RuntimeError> {
+        self.count_step()?;
+
+        match statement {
+            Statement::VarDeclaration { name, value } => {
+                if self.environment.is_constant(name) {
+                    return Err(RuntimeError::AssignToConst(name.clone()));
+                }
+                let val = self.evaluate_expression(value)?;
+                self.environment.define(name.clone(), val);
+                Ok(Value::Null)
+            }
+            Statement::TupleAssignment { names, values } => {
+                let mut evaluated = Vec::with_capacity(values.len());
+                for value in values {
+                    evaluated.push(self.evaluate_expression(value)?);
+                }
+                // Validate every target first so a bad name leaves all of
+                // them untouched.
+                for name in names {
+                    self.environment.check_assignable(name)?;
+                }
+                for (name, val) in names.iter().zip(evaluated) {
+                    self.environment.assign(name, val)?;
+                }
+                Ok(Value::Null)
+            }
+            Statement::Switch {
+                subject,
+                cases,
+                default,
+            } => {
+                let subject = self.evaluate_expression(subject)?;
+
+                for (value, body) in cases {
+                    let value = self.evaluate_expression(value)?;
+                    let matched =
+                        self.evaluate_binary_op(subject.clone(), BinaryOp::Equal, value)?;
+                    if matched == Value::Boolean(true) {
+                        return self.execute_block(body);
+                    }
+                }
+
+                match default {
+                    Some(body) => self.execute_block(body),
+                    None => Ok(Value::Null),
+                }
+            }
+            Statement::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => match self.evaluate_expression(condition)? {
+                Value::Boolean(true) => self.execute_block(then_branch),
+                Value::Boolean(false) => match else_branch {
+                    Some(body) => self.execute_block(body),
+                    None => Ok(Value::Null),
+                },
+                other => Err(RuntimeError::TypeError(format!(
+                    "if condition must be a boolean, got {}",
+                    other.type_name()
+                ))),
+            },
+            Statement::While { condition, body } => {
+                let mut iterations = 0;
+                loop {
+                    match self.evaluate_expression(condition)? {
+                        Value::Boolean(true) => {}
+                        Value::Boolean(false) => break,
+                        other => {
+                            return Err(RuntimeError::TypeError(format!(
+                                "while condition must be a boolean, got {}",
+                                other.type_name()
+                            )));
+                        }
+                    }
+
+                    iterations += 1;
+                    if let Some(limit) = self.limits.max_loop_iterations
+                        && iterations > limit
+                    {
+                        return Err(RuntimeError::IterationLimitExceeded(limit));
+                    }
+
+                    self.execute_block(body)?;
+                }
+                Ok(Value::Null)
+            }
+            Statement::Expression(expr) => self.evaluate_expression(expr),
+        }
+    }
+
+    fn execute_block(&mut self, statements: &[Statement]) -> Result<Value, RuntimeError> {
+        let mut last = Value::Null;
+        for statement in statements {
+            last = self.execute_statement(statement)?;
+        }
+        Ok(last)
+    }
+
+    fn count_step(&mut self) -> Result<(), RuntimeError> {
+        self.steps += 1;
+        if let Some(deadline) = self.deadline
+            && Instant::now() >= deadline
+        {
+            return Err(RuntimeError::Timeout);
+        }
+        match self.limits.max_steps {
+            Some(limit) if self.steps > limit => Err(RuntimeError::StepLimitExceeded(limit)),
+            _ => Ok(()),
+        }
+    }
+
+    fn evaluate_expression(&mut self, expression: &Expression) -> Result<Value, RuntimeError> {
+        self.count_step()?;
+
+        if let Some(limit) = self.limits.max_depth
+            && self.depth >= limit
+        {
+            return Err(RuntimeError::DepthLimitExceeded(limit));
+        }
+
+        self.depth += 1;
+        let result = self.evaluate_nested_expression(expression);
+        self.depth -= 1;
+        result
+    }
+
+    fn evaluate_nested_expression(
+        &mut self,
+        expression: &Expression,
+    ) -> Result<Value, RuntimeError> {
+        match expression {
+            Expression::Literal(literal) => Ok(self.literal_to_value(literal)),
+            Expression::Identifier(name) => self.environment.get(name),
+            Expression::FunctionCall { name, args } => self.call_function(name, args),
+            Expression::Assignment { name, value } => {
+                let val = self.evaluate_expression(value)?;
+                self.environment.assign(name, val.clone())?;
+                Ok(val)
+            }
+            Expression::Binary { left, op, right } => {
+                let left_val = self.evaluate_expression(left)?;
+                let right_val = self.evaluate_expression(right)?;
+                self.evaluate_binary_op(left_val, op.clone(), right_val)
+            }
+        }
+    }
+
+    fn literal_to_value(&self, literal: &Literal) -> Value {
+        match literal {
+            Literal::String(s) => Value::String(s.clone()),
+            Literal::Number(n) => Value::Number(*n),
+            Literal::Boolean(b) => Value::Boolean(*b),
+        }
+    }
+
+    fn evaluate_binary_op(
+        &self,
+        left: Value,
+        op: BinaryOp,
+        right: Value,
+    ) -> Result<Value, RuntimeError> {
+        match (left, right) {
+            (Value::Number(l), Value::Number(r)) => match op {
+                BinaryOp::Add => self.arithmetic_result(l, r, l + r),
+                BinaryOp::Subtract => self.arithmetic_result(l, r, l - r),
+                BinaryOp::Multiply => self.arithmetic_result(l, r, l * r),
+                BinaryOp::Divide => {
+                    if r == 0.0 {
+                        return Err(RuntimeError::TypeError("Division by zero".to_string()));
+                    }
+                    self.arithmetic_result(l, r, l / r)
+                }
+                BinaryOp::Modulo => {
+                    if r == 0.0 {
+                        return Err(RuntimeError::TypeError("Modulo by zero".to_string()));
+                    }
+                    Ok(Value::Number(l % r))
+                }
+                BinaryOp::Equal => Ok(Value::Boolean(self.numbers_equal(l, r))),
+                BinaryOp::NotEqual => Ok(Value::Boolean(!self.numbers_equal(l, r))),
+                BinaryOp::BitAnd => Ok(Value::Number((to_integer(l)? & to_integer(r)?) as f64)),
+                BinaryOp::BitOr => Ok(Value::Number((to_integer(l)? | to_integer(r)?) as f64)),
+                BinaryOp::BitXor => Ok(Value::Number((to_integer(l)? ^ to_integer(r)?) as f64)),
+                BinaryOp::ShiftLeft => {
+                    let (value, amount) = (to_integer(l)?, shift_amount(r)?);
+                    let shifted = value << amount;
+                    if shifted >> amount != value {
+                        return Err(RuntimeError::Overflow);
+                    }
+                    Ok(Value::Number(shifted as f64))
+                }
+                BinaryOp::ShiftRight => {
+                    Ok(Value::Number((to_integer(l)? >> shift_amount(r)?) as f64))
+                }
+            },
+            (l, r) if matches!(op, BinaryOp::Equal) => Ok(Value::Boolean(l == r)),
+            (l, r) if matches!(op, BinaryOp::NotEqual) => Ok(Value::Boolean(l != r)),
+            (Value::String(l), Value::String(r)) if matches!(op, BinaryOp::Add) => {
+                Ok(Value::String(format!("{l}{r}")))
+            }
+            (l, r) => Err(RuntimeError::TypeError(format!(
+                "Cannot apply {:?} to {} and {}",
+                op,
+                l.type_name(),
+                r.type_name()
+            ))),
+        }
+    }
+
+    fn arithmetic_result(&self, left: f64, right: f64, result: f64) -> Result<Value, RuntimeError> {
+        if self.error_on_infinity && result.is_infinite() && left.is_finite() && right.is_finite() {
+            return Err(RuntimeError::Overflow);
+        }
+        Ok(Value::Number(result))
+    }
+
+    fn numbers_equal(&self, left: f64, right: f64) -> bool {
+        // Exact equality first: `Infinity - Infinity` is NaN, which no
+        // epsilon would accept.
+        left == right || (left - right).abs() <= self.number_epsilon
+    }
+
+    fn call_function(&mut self, name: &str, args: &[Expression]) -> Result<Value, RuntimeError> {
+        if let Some(limit) = self.limits.max_call_args
+            && args.len() > limit
+        {
+            return Err(RuntimeError::ArgumentLimitExceeded(limit));
+        }
+
+        if !self.debug_assertions
+            && self.builtins_enabled
+            && self.environment.canonical_name(name) == "debug_assert"
+            && !self.natives.contains_key("debug_assert")
+        {
+            return Ok(Value::Null);
+        }
+
+        let mut arg_values = Vec::new();
+        for arg in args {
+            arg_values.push(self.evaluate_expression(arg)?);
+        }
+
+        let canonical = self.environment.canonical_name(name);
+
+        if let Some(native) = self.natives.get(canonical.as_ref()) {
+            return native(arg_values);
+        }
+
+        if !self.builtins_enabled {
+            return Err(RuntimeError::UndefinedFunction(name.to_string()));
+        }
+
+        match canonical.as_ref() {
+            "print" => self.builtin_print(arg_values),
+            "println" => self.builtin_println(arg_values),
+            "print_sep" => self.builtin_print_sep(arg_values),
+            "println_sep" => self.builtin_println_sep(arg_values),
+            "flush" => self.builtin_flush(arg_values),
+            "typeof" => self.builtin_typeof(arg_values),
+            "env" => self.builtin_env(arg_values),
+            "try_num" => self.builtin_try_num(arg_values),
+            "num" => self.builtin_num(arg_values),
+            "compare" => self.builtin_compare(arg_values),
+            "strict_eq" => self.builtin_strict_eq(arg_values),
+            "join_path" => self.builtin_join_path(arg_values),
+            "pad_left" => self.builtin_pad("pad_left", arg_values, true),
+            "pad_right" => self.builtin_pad("pad_right", arg_values, false),
+            "assert_approx" => self.builtin_assert_approx(arg_values),
+            "assert_type" => self.builtin_assert_type(arg_values),
+            "debug_assert" => self.builtin_debug_assert(arg_values),
+            "sleep" => self.builtin_sleep(arg_values),
+            "round_to" => self.builtin_round_to(arg_values),
+            "sci" => self.builtin_sci(arg_values),
+            "format_number" => self.builtin_format_number(arg_values),
+            "coalesce" => self.builtin_coalesce(arg_values),
+            "eval" => self.builtin_eval(arg_values),
+            "char_at" => self.builtin_char_at(arg_values),
+            "starts_with" => self.builtin_affix("starts_with", arg_values, false),
+            "ends_with" => self.builtin_affix("ends_with", arg_values, true),
+            "trim_start" => self.builtin_trim("trim_start", arg_values, true),
+            "trim_end" => self.builtin_trim("trim_end", arg_values, false),
+            #[cfg(feature = "base64")]
+            "to_base64" => self.builtin_to_base64(arg_values),
+            #[cfg(feature = "base64")]
+            "from_base64" => self.builtin_from_base64(arg_values),
+            #[cfg(feature = "fs")]
+            "read_file" => self.builtin_read_file(arg_values),
+            #[cfg(feature = "fs")]
+            "write_file" => self.builtin_write_file(arg_values),
+            _ => Err(RuntimeError::UndefinedFunction(name.to_string())),
+        }
+    }
+
+    fn builtin_print(&mut self, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        self.write_joined(&args, " ")?;
+        Ok(Value::Null)
+    }
+
+    fn builtin_println(&mut self, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        self.builtin_print(args)?;
+        self.write_output("\n")?;
+        Ok(Value::Null)
+    }
+
+    fn builtin_print_sep(&mut self, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        self.print_with_separator("print_sep", args)
+    }
+
+    fn builtin_println_sep(&mut self, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        self.print_with_separator("println_sep", args)?;
+        self.write_output("\n")?;
+        Ok(Value::Null)
+    }
+
+    fn print_with_separator(
+        &mut self,
+        function: &str,
+        args: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        let Some((separator, rest)) = args.split_first() else {
+            return Err(RuntimeError::ArityMismatch {
+                function: function.to_string(),
+                expected: 1,
+                found: 0,
+            });
+        };
+
+        let Value::String(separator) = separator else {
+            return Err(RuntimeError::TypeError(format!(
+                "{function} expects a string separator, got {}",
+                separator.type_name()
+            )));
+        };
+
+        self.write_joined(rest, separator)?;
+        Ok(Value::Null)
+    }
+
+    fn write_joined(&mut self, args: &[Value], separator: &str) -> Result<(), RuntimeError> {
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                self.write_output(separator)?;
+            }
+            let text = match arg {
+                Value::Number(n) => self.number_format.format(*n),
+                other => other.to_string(),
+            };
+            self.write_output(&text)?;
+        }
+        Ok(())
+    }
+
+    fn write_output(&mut self, text: &str) -> Result<(), RuntimeError> {
+        if self.output_buffered {
+            self.pending_output.extend_from_slice(text.as_bytes());
+            return Ok(());
+        }
+
+        self.output
+            .0
+            .borrow_mut()
+            .write_all(text.as_bytes())
+            .map_err(|e| RuntimeError::IoError(e.to_string()))
+    }
+
+    fn flush_output(&mut self) -> Result<(), RuntimeError> {
+        let pending = std::mem::take(&mut self.pending_output);
+        let mut output = self.output.0.borrow_mut();
+        output
+            .write_all(&pending)
+            .and_then(|()| output.flush())
+            .map_err(|e| RuntimeError::IoError(e.to_string()))
+    }
+
+    fn builtin_flush(&mut self, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        if !args.is_empty() {
+            return Err(RuntimeError::ArityMismatch {
+                function: "flush".to_string(),
+                expected: 0,
+                found: args.len(),
+            });
+        }
+
+        self.flush_output()?;
+        Ok(Value::Null)
+    }
+
+    fn builtin_eval(&mut self, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        if args.len() != 1 {
+            return Err(RuntimeError::ArityMismatch {
+                function: "eval".to_string(),
+                expected: 1,
+                found: args.len(),
+            });
+        }
+
+        if !self.eval_enabled {
+            return Err(RuntimeError::PermissionDenied("eval".to_string()));
+        }
+
+        let source = args[0].as_string()?;
+        let program = Parser::new(Lexer::new(source))
+            .parse()
+            .map_err(RuntimeError::Parse)?;
+        self.execute_block(&program.statements)
+    }
+
+    fn builtin_typeof(&self, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        if args.len() != 1 {
+            return Err(RuntimeError::ArityMismatch {
+                function: "typeof".to_string(),
+                expected: 1,
+                found: args.len(),
+            });
+        }
+
+        Ok(Value::String(args[0].type_name().to_string()))
+    }
+
+    fn builtin_compare(&self, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        if args.len() != 2 {
+            return Err(RuntimeError::ArityMismatch {
+                function: "compare".to_string(),
+                expected: 2,
+                found: args.len(),
+            });
+        }
+
+        let ordering = match (&args[0], &args[1]) {
+            (Value::Number(l), Value::Number(r)) => l.partial_cmp(r),
+            (Value::String(l), Value::String(r)) => Some(l.cmp(r)),
+            _ => None,
+        };
+
+        match ordering {
+            Some(ordering) => Ok(Value::Number(ordering as i8 as f64)),
+            None => Err(RuntimeError::TypeError(format!(
+                "Cannot compare {} and {}",
+                args[0].type_name(),
+                args[1].type_name()
+            ))),
+        }
+    }
+
+    fn builtin_pad(
+        &self,
+        function: &str,
+        args: Vec<Value>,
+        left: bool,
+    ) -> Result<Value, RuntimeError> {
+        if !(2..=3).contains(&args.len()) {
+            // The fill is optional, so report the nearest valid count.
+            return Err(RuntimeError::ArityMismatch {
+                function: function.to_string(),
+                expected: args.len().clamp(2, 3),
+                found: args.len(),
+            });
+        }
+
+        let text = args[0].as_string()?;
+        let width = args[1].as_index()?;
+
+        let fill = match args.get(2) {
+            None => ' ',
+            Some(Value::String(fill)) if fill.chars().count() == 1 => fill.chars().next().unwrap(),
+            Some(other) => {
+                return Err(RuntimeError::TypeError(format!(
+                    "{function} expects a single-character fill, got {other}"
+                )));
+            }
+        };
+
+        let padding: String =
+            iter::repeat_n(fill, width.saturating_sub(text.chars().count())).collect();
+        Ok(Value::String(if left {
+            format!("{padding}{text}")
+        } else {
+            format!("{text}{padding}")
+        }))
+    }
+
+    fn builtin_char_at(&self, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        if args.len() != 2 {
+            return Err(RuntimeError::ArityMismatch {
+                function: "char_at".to_string(),
+                expected: 2,
+                found: args.len(),
+            });
+        }
+
+        let text = args[0].as_string()?;
+        let index = args[1].as_index()?;
+
+        match text.chars().nth(index) {
+            Some(ch) => Ok(Value::String(ch.to_string())),
+            None => Err(RuntimeError::TypeError(format!(
+                "char_at index {index} is out of range for a string of {} characters",
+                text.chars().count()
+            ))),
+        }
+    }
+
+    fn builtin_affix(
+        &self,
+        function: &str,
+        args: Vec<Value>,
+        suffix: bool,
+    ) -> Result<Value, RuntimeError> {
+        if args.len() != 2 {
+            return Err(RuntimeError::ArityMismatch {
+                function: function.to_string(),
+                expected: 2,
+                found: args.len(),
+            });
+        }
+
+        let (text, affix) = (args[0].as_string()?, args[1].as_string()?);
+        Ok(Value::Boolean(if suffix {
+            text.ends_with(affix)
+        } else {
+            text.starts_with(affix)
+        }))
+    }
+
+    fn builtin_trim(
+        &self,
+        function: &str,
+        args: Vec<Value>,
+        start: bool,
+    ) -> Result<Value, RuntimeError> {
+        if args.len() != 1 {
+            return Err(RuntimeError::ArityMismatch {
+                function: function.to_string(),
+                expected: 1,
+                found: args.len(),
+            });
+        }
+
+        let text = args[0].as_string()?;
+        Ok(Value::String(if start {
+            text.trim_start().to_string()
+        } else {
+            text.trim_end().to_string()
+        }))
+    }
+
+    fn builtin_join_path(&self, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        if args.is_empty() {
+            return Err(RuntimeError::ArityMismatch {
+                function: "join_path".to_string(),
+                expected: 1,
+                found: 0,
+            });
+        }
+
+        let mut path = std::path::PathBuf::new();
+        for arg in &args {
+            match arg {
+                Value::String(segment) => path.push(segment),
+                other => {
+                    return Err(RuntimeError::TypeError(format!(
+                        "join_path expects string segments, got {}",
+                        other.type_name()
+                    )));
+                }
+            }
+        }
+
+        Ok(Value::String(path.to_string_lossy().into_owned()))
+    }
+
+    fn builtin_strict_eq(&self, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        if args.len() != 2 {
+            return Err(RuntimeError::ArityMismatch {
+                function: "strict_eq".to_string(),
+                expected: 2,
+                found: args.len(),
+            });
+        }
+
+        let equal = match (&args[0], &args[1]) {
+            (Value::Number(l), Value::Number(r)) => l.to_bits() == r.to_bits(),
+            (l, r) => l == r,
+        };
+
+        Ok(Value::Boolean(equal))
+    }
+
+    fn builtin_debug_assert(&self, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        if args.len() != 1 {
+            return Err(RuntimeError::ArityMismatch {
+                function: "debug_assert".to_string(),
+                expected: 1,
+                found: args.len(),
+            });
+        }
+
+        if args[0].as_bool()? {
+            Ok(Value::Null)
+        } else {
+            Err(RuntimeError::AssertionFailed(
+                "debug_assert condition was false".to_string(),
+            ))
+        }
+    }
+
+    fn builtin_assert_type(&self, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        if args.len() != 2 {
+            return Err(RuntimeError::ArityMismatch {
+                function: "assert_type".to_string(),
+                expected: 2,
+                found: args.len(),
+            });
+        }
+
+        let mut args = args.into_iter();
+        let value = args.next().unwrap();
+        let expected = match args.next().unwrap() {
+            Value::String(expected) => expected,
+            other => {
+                return Err(RuntimeError::TypeError(format!(
+                    "assert_type expects a type name string, got {}",
+                    other.type_name()
+                )));
+            }
+        };
+
+        let actual = value.type_name();
+        if actual != expected {
+            return Err(RuntimeError::AssertionFailed(format!(
+                "expected a value of type {expected}, got {actual} ({value})"
+            )));
+        }
+
+        Ok(value)
+    }
+
+    fn builtin_sleep(&self, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        if args.len() != 1 {
+            return Err(RuntimeError::ArityMismatch {
+                function: "sleep".to_string(),
+                expected: 1,
+                found: args.len(),
+            });
+        }
+
+        let duration = match &args[0] {
+            Value::Number(seconds) if *seconds >= 0.0 => Duration::try_from_secs_f64(*seconds)
+                .map_err(|_| {
+                    RuntimeError::TypeError(format!("sleep duration {seconds} is too large"))
+                })?,
+            Value::Number(seconds) => {
+                return Err(RuntimeError::TypeError(format!(
+                    "sleep expects a non-negative number of seconds, got {seconds}"
+                )));
+            }
+            other => {
+                return Err(RuntimeError::TypeError(format!(
+                    "sleep expects a number, got {}",
+                    other.type_name()
+                )));
+            }
+        };
+
+        if let Some(limit) = self.limits.max_sleep
+            && duration > limit
+        {
+            return Err(RuntimeError::SleepLimitExceeded(limit));
+        }
+
+        if let Some(deadline) = self.deadline {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if duration >= remaining {
+                std::thread::sleep(remaining);
+                return Err(RuntimeError::Timeout);
+            }
+        }
+
+        std::thread::sleep(duration);
+        Ok(Value::Null)
+    }
+
+    fn builtin_assert_approx(&self, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        if args.len() != 3 {
+            return Err(RuntimeError::ArityMismatch {
+                function: "assert_approx".to_string(),
+                expected: 3,
+                found: args.len(),
+            });
+        }
+
+        let (actual, expected, epsilon) = match (&args[0], &args[1], &args[2]) {
+            (Value::Number(a), Value::Number(e), Value::Number(eps)) => (*a, *e, *eps),
+            (a, e, eps) => {
+                return Err(RuntimeError::TypeError(format!(
+                    "assert_approx expects three numbers, got {}, {} and {}",
+                    a.type_name(),
+                    e.type_name(),
+                    eps.type_name()
+                )));
+            }
+        };
+
+        if (actual - expected).abs() <= epsilon {
+            Ok(Value::Null)
+        } else {
+            Err(RuntimeError::AssertionFailed(format!(
+                "expected {} to be within {} of {}",
+                args[0], args[2], args[1]
+            )))
+        }
+    }
+
+    fn builtin_num(&self, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        if args.len() != 1 {
+            return Err(RuntimeError::ArityMismatch {
+                function: "num".to_string(),
+                expected: 1,
+                found: args.len(),
+            });
+        }
+
+        match &args[0] {
+            Value::Number(n) => Ok(Value::Number(*n)),
+            Value::String(s) => parse_number_str(s.trim())
+                .map(Value::Number)
+                .ok_or_else(|| {
+                    RuntimeError::TypeError(format!("num cannot parse \"{s}\" as a number"))
+                }),
+            other => Err(RuntimeError::TypeError(format!(
+                "num expects a string or number, got {}",
+                other.type_name()
+            ))),
+        }
+    }
+
+    fn builtin_try_num(&self, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        if args.len() != 1 {
+            return Err(RuntimeError::ArityMismatch {
+                function: "try_num".to_string(),
+                expected: 1,
+                found: args.len(),
+            });
+        }
+
+        Ok(match &args[0] {
+            Value::Number(n) => Value::Number(*n),
+            Value::String(s) => parse_number_str(s.trim())
+                .map(Value::Number)
+                .unwrap_or(Value::Null),
+            Value::Boolean(_) | Value::Bytes(_) | Value::Null => Value::Null,
+        })
+    }
+
+    fn builtin_round_to(&self, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        if args.len() != 3 {
+            return Err(RuntimeError::ArityMismatch {
+                function: "round_to".to_string(),
+                expected: 3,
+                found: args.len(),
+            });
+        }
+
+        let n = args[0].as_number()?;
+        let places = args[1].as_index()? as i32;
+        let mode = args[2].as_string()?;
+
+        let round: fn(f64) -> f64 = match mode {
+            // Ties go towards positive infinity, so -2.5 rounds to -2.
+            // `x - floor` is exact, unlike `(x + 0.5).floor()`.
+            "half_up" => |x: f64| {
+                let floor = x.floor();
+                if x - floor >= 0.5 { floor + 1.0 } else { floor }
+            },
+            "half_even" => f64::round_ties_even,
+            other => {
+                return Err(RuntimeError::TypeError(format!(
+                    "round_to mode must be \"half_up\" or \"half_even\", got \"{other}\""
+                )));
+            }
+        };
+
+        // Past f64's range of decimal places there is nothing left to round.
+        let scale = 10f64.powi(places);
+        let scaled = n * scale;
+        if !scale.is_finite() || !scaled.is_finite() {
+            return Ok(Value::Number(n));
+        }
+
+        Ok(Value::Number(round(scaled) / scale))
+    }
+
+    /// Unlike a short-circuiting operator, every argument has already been
+    /// evaluated by the time this runs.
+    fn builtin_coalesce(&self, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        Ok(args
+            .into_iter()
+            .find(|value| *value != Value::Null)
+            .unwrap_or(Value::Null))
+    }
+
+    fn builtin_format_number(&self, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        if args.len() != 1 {
+            return Err(RuntimeError::ArityMismatch {
+                function: "format_number".to_string(),
+                expected: 1,
+                found: args.len(),
+            });
+        }
+
+        let n = args[0].as_number()?;
+        Ok(Value::String(self.number_format.format(n)))
+    }
+
+    fn builtin_sci(&self, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        if args.len() != 2 {
+            return Err(RuntimeError::ArityMismatch {
+                function: "sci".to_string(),
+                expected: 2,
+                found: args.len(),
+            });
+        }
+
+        let n = args[0].as_number()?;
+        let Some(precision) = args[1].as_index()?.checked_sub(1) else {
+            return Err(RuntimeError::TypeError(
+                "sci expects a positive digit count, got 0".to_string(),
+            ));
+        };
+        Ok(Value::String(format!("{n:.precision$e}")))
+    }
+
+    #[cfg(feature = "base64")]
+    fn builtin_to_base64(&self, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        if args.len() != 1 {
+            return Err(RuntimeError::ArityMismatch {
+                function: "to_base64".to_string(),
+                expected: 1,
+                found: args.len(),
+            });
+        }
+
+        match &args[0] {
+            Value::Bytes(bytes) => Ok(Value::String(encode_base64(bytes))),
+            other => Err(RuntimeError::TypeError(format!(
+                "to_base64 expects bytes, got {}",
+                other.type_name()
+            ))),
+        }
+    }
+
+    #[cfg(feature = "base64")]
+    fn builtin_from_base64(&self, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        if args.len() != 1 {
+            return Err(RuntimeError::ArityMismatch {
+                function: "from_base64".to_string(),
+                expected: 1,
+                found: args.len(),
+            });
+        }
+
+        match &args[0] {
+            Value::String(text) => decode_base64(text)
+                .map(Value::Bytes)
+                .ok_or_else(|| RuntimeError::TypeError(format!("'{text}' is not valid base64"))),
+            other => Err(RuntimeError::TypeError(format!(
+                "from_base64 expects a string, got {}",
+                other.type_name()
+            ))),
+        }
+    }
+
+    fn builtin_env(&self, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        if args.len() != 1 {
+            return Err(RuntimeError::ArityMismatch {
+                function: "env".to_string(),
+                expected: 1,
+                found: args.len(),
+            });
+        }
+
+        let name = match &args[0] {
+            Value::String(name) => name,
+            other => {
+                return Err(RuntimeError::TypeError(format!(
+                    "env expects a string name, got {}",
+                    other.type_name()
+                )));
+            }
+        };
+
+        if !self.allowed_env_vars.contains(name) {
+            return Err(RuntimeError::PermissionDenied(format!(
+                "environment variable '{name}'"
+            )));
+        }
+
+        Ok(std::env::var(name)
+            .map(Value::String)
+            .unwrap_or(Value::Null))
+    }
+
+    #[cfg(feature = "fs")]
+    fn builtin_read_file(&self, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        if args.len() != 1 {
+            return Err(RuntimeError::ArityMismatch {
+                function: "read_file".to_string(),
+                expected: 1,
+                found: args.len(),
+            });
+        }
+
+        let path = match &args[0] {
+            Value::String(path) => self.sandboxed_path(path)?,
+            other => {
+                return Err(RuntimeError::TypeError(format!(
+                    "read_file expects a string path, got {}",
+                    other.type_name()
+                )));
+            }
+        };
+
+        std::fs::read_to_string(&path)
+            .map(Value::String)
+            .map_err(|e| RuntimeError::IoError(format!("{}: {e}", path.display())))
+    }
+
+    #[cfg(feature = "fs")]
+    fn builtin_write_file(&self, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        if args.len() != 2 {
+            return Err(RuntimeError::ArityMismatch {
+                function: "write_file".to_string(),
+                expected: 2,
+                found: args.len(),
+            });
+        }
+
+        let (path, contents) = match (&args[0], &args[1]) {
+            (Value::String(path), Value::String(contents)) => {
+                (self.sandboxed_path(path)?, contents)
+            }
+            (l, r) => {
+                return Err(RuntimeError::TypeError(format!(
+                    "write_file expects a string path and string contents, got {} and {}",
+                    l.type_name(),
+                    r.type_name()
+                )));
+            }
+        };
+
+        std::fs::write(&path, contents)
+            .map(|_| Value::Null)
+            .map_err(|e| RuntimeError::IoError(format!("{}: {e}", path.display())))
+    }
+
+    #[cfg(feature = "fs")]
+    fn sandboxed_path(&self, path: &str) -> Result<PathBuf, RuntimeError> {
+        let denied = || RuntimeError::PermissionDenied(format!("file access to '{path}'"));
+        let io_error = |e: std::io::Error| RuntimeError::IoError(format!("{path}: {e}"));
+
+        let root = self
+            .fs_root
+            .as_ref()
+            .ok_or_else(denied)?
+            .canonicalize()
+            .map_err(io_error)?;
+
+        let candidate = root.join(path);
+        let file_name = candidate.file_name().ok_or_else(denied)?;
+        let parent = candidate
+            .parent()
+            .unwrap_or(Path::new("/"))
+            .canonicalize()
+            .map_err(io_error)?;
+
+        if !parent.starts_with(&root) {
+            return Err(denied());
+        }
+
+        // An existing final component may be a symlink pointing outside the
+        // root, so resolve it too. Dangling links can't be resolved and are
+        // refused, since writing through them would create the target.
+        let resolved = parent.join(file_name);
+        if resolved.symlink_metadata().is_err() {
+            return Ok(resolved);
+        }
+        let resolved = resolved.canonicalize().map_err(|_| denied())?;
+        if !resolved.starts_with(&root) {
+            return Err(denied());
+        }
+
+        Ok(resolved)
+    }
+
+    pub fn get_variables(&self) -> &HashMap<String, Value> {
+        &self.environment.variables
+    }
+
+    pub fn dump_state(&self) -> StateSnapshot {
+        StateSnapshot {
+            variables: self.environment.variables.clone(),
+            constants: self.environment.constants.clone(),
+        }
+    }
+
+    pub fn restore_state(&mut self, snapshot: StateSnapshot) {
+        self.environment.variables = snapshot.variables;
+        self.environment.constants = snapshot.constants;
+    }
+}
+
+/// Captures variable and constant bindings only; native functions, limits
+/// and other interpreter settings are left to the host to reapply. With the
+/// `serde` feature the snapshot can be serialized for checkpointing.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StateSnapshot {
+    pub variables: HashMap<String, Value>,
+    pub constants: HashSet<String>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn to_integer(n: f64) -> Result<i64, RuntimeError> {
+    // `i64::MAX as f64` rounds up to 2^63, which is out of range.
+    if n.fract() == 0.0 && n >= i64::MIN as f64 && n < 9_223_372_036_854_775_808.0 {
+        Ok(n as i64)
+    } else {
+        Err(RuntimeError::TypeError(format!(
+            "Bitwise operations require integers, got {}",
+            Value::Number(n)
+        )))
+    }
+}
+
+fn shift_amount(n: f64) -> Result<u32, RuntimeError> {
+    match to_integer(n)? {
+        amount @ 0..64 => Ok(amount as u32),
+        _ => Err(RuntimeError::TypeError(format!(
+            "Shift amount must be between 0 and 63, got {}",
+            Value::Number(n)
+        ))),
+    }
+}
+
+#[cfg(feature = "base64")]
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+#[cfg(feature = "base64")]
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let group = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |acc, (i, &byte)| acc | (byte as u32) << (16 - 8 * i));
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (group >> (18 - 6 * i)) & 0x3f;
+                encoded.push(BASE64_ALPHABET[index as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+#[cfg(feature = "base64")]
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(4) {
+        return None;
+    }
+
+    let data = text
+        .strip_suffix("==")
+        .or_else(|| text.strip_suffix('='))
+        .unwrap_or(text);
+    if data.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut decoded = Vec::with_capacity(data.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for ch in data.bytes() {
+        let value = BASE64_ALPHABET.iter().position(|&c| c == ch)? as u32;
+        buffer = (buffer << 6) | value;
+        bits += 6;
+
+        if bits >= 8 {
+            bits -= 8;
+            decoded.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+
+    Some(decoded)
 }
@@ -1,4 +1,4 @@
-use std::{iter, num::ParseFloatError, str};
+use std::{cell::RefCell, collections::VecDeque, io::BufRead, rc::Rc};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Literal {
@@ -10,31 +10,184 @@ pub enum Literal {
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     Var,
+    Switch,
+    Case,
+    Default,
+    If,
+    Else,
+    While,
     Identifier(String),
     Equals,
+    EqualEqual,
+    NotEqual,
     SemiColon,
     Literal(Literal),
     LeftParen,
     RightParen,
+    LeftBrace,
+    RightBrace,
     Comma,
+    Colon,
+    Dot,
     Add,
     Subtract,
     Multiply,
     Divide,
+    Modulo,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    Error(String),
     EOF,
 }
 
+pub fn parse_number_str(text: &str) -> Option<f64> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut cleaned = String::with_capacity(text.len());
+
+    for (i, &ch) in chars.iter().enumerate() {
+        match ch {
+            '_' => {
+                let between_digits = i > 0
+                    && chars[i - 1].is_ascii_digit()
+                    && chars.get(i + 1).is_some_and(char::is_ascii_digit);
+                if !between_digits {
+                    return None;
+                }
+            }
+            '0'..='9' | '.' | 'e' | 'E' | '+' | '-' => cleaned.push(ch),
+            _ => return None,
+        }
+    }
+
+    cleaned.parse().ok()
+}
+
+struct CharStream<'a> {
+    source: Box<dyn Iterator<Item = char> + 'a>,
+    lookahead: VecDeque<char>,
+}
+
+impl<'a> CharStream<'a> {
+    fn new(source: impl Iterator<Item = char> + 'a) -> Self {
+        Self {
+            source: Box::new(source),
+            lookahead: VecDeque::new(),
+        }
+    }
+
+    fn fill(&mut self, len: usize) {
+        while self.lookahead.len() < len {
+            match self.source.next() {
+                Some(ch) => self.lookahead.push_back(ch),
+                None => break,
+            }
+        }
+    }
+
+    fn peek(&mut self) -> Option<&char> {
+        self.fill(1);
+        self.lookahead.front()
+    }
+
+    fn peek_nth(&mut self, n: usize) -> Option<char> {
+        self.fill(n + 1);
+        self.lookahead.get(n).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        self.fill(1);
+        self.lookahead.pop_front()
+    }
+}
+
+// Read failures (including invalid UTF-8) end the character stream; the
+// message is left in `error` so the lexer can report it instead of EOF.
+struct ReaderChars<R> {
+    reader: R,
+    line: String,
+    pos: usize,
+    failed: bool,
+    error: Rc<RefCell<Option<String>>>,
+}
+
+impl<R: BufRead> Iterator for ReaderChars<R> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        loop {
+            if let Some(ch) = self.line[self.pos..].chars().next() {
+                self.pos += ch.len_utf8();
+                return Some(ch);
+            }
+
+            if self.failed {
+                return None;
+            }
+            self.line.clear();
+            self.pos = 0;
+            match self.reader.read_line(&mut self.line) {
+                Ok(0) => return None,
+                Ok(_) => {}
+                Err(e) => {
+                    self.failed = true;
+                    *self.error.borrow_mut() = Some(format!("read error: {e}"));
+                    return None;
+                }
+            }
+        }
+    }
+}
+
 pub struct Lexer<'a> {
-    chars: iter::Peekable<str::Chars<'a>>,
+    chars: CharStream<'a>,
     current_token: Token,
+    next_token: Token,
+    tokens: Option<VecDeque<Token>>,
+    read_error: Rc<RefCell<Option<String>>>,
 }
 
 impl<'a> Lexer<'a> {
     pub fn new(input: &'a str) -> Self {
+        Self::from_chars(CharStream::new(input.chars()), None, Rc::default())
+    }
+
+    pub fn from_reader(reader: impl BufRead + 'a) -> Self {
+        let read_error = Rc::default();
+        let chars = ReaderChars {
+            reader,
+            line: String::new(),
+            pos: 0,
+            failed: false,
+            error: Rc::clone(&read_error),
+        };
+        Self::from_chars(CharStream::new(chars), None, read_error)
+    }
+
+    pub fn from_tokens(tokens: Vec<Token>) -> Lexer<'static> {
+        Lexer::from_chars(
+            CharStream::new("".chars()),
+            Some(tokens.into()),
+            Rc::default(),
+        )
+    }
+
+    fn from_chars(
+        chars: CharStream<'a>,
+        tokens: Option<VecDeque<Token>>,
+        read_error: Rc<RefCell<Option<String>>>,
+    ) -> Self {
         let mut lexer = Self {
-            chars: input.chars().peekable(),
+            chars,
             current_token: Token::EOF,
+            next_token: Token::EOF,
+            tokens,
+            read_error,
         };
+        lexer.skip_shebang();
+        lexer.advance();
         lexer.advance();
         lexer
     }
@@ -44,12 +197,42 @@ impl<'a> Lexer<'a> {
     }
 
     pub fn advance(&mut self) {
-        self.current_token = self.next_token();
+        let next = self.lex_token();
+        self.current_token = std::mem::replace(&mut self.next_token, next);
+    }
+
+    pub fn peek_token(&self) -> Token {
+        self.next_token.clone()
+    }
+
+    fn skip_shebang(&mut self) {
+        if self.chars.peek_nth(0) == Some('#') && self.chars.peek_nth(1) == Some('!') {
+            self.skip_line();
+        }
+    }
+
+    // Block comments do not nest, so `/* /* */ */` leaves a dangling `*/`.
+    fn skip_block_comment(&mut self) -> bool {
+        while let Some(ch) = self.chars.next() {
+            if ch == '*' && self.chars.peek() == Some(&'/') {
+                self.chars.next();
+                return true;
+            }
+        }
+        false
+    }
+
+    fn skip_line(&mut self) {
+        while let Some(ch) = self.chars.next() {
+            if ch == '\n' {
+                break;
+            }
+        }
     }
 
     fn skip_whitespace(&mut self) {
         while let Some(&ch) = self.chars.peek() {
-            if ch.is_whitespace() {
+            if ch.is_whitespace() || (ch == '\\' && self.at_line_continuation()) {
                 self.chars.next();
             } else {
                 break;
@@ -57,7 +240,27 @@ impl<'a> Lexer<'a> {
         }
     }
 
-    fn read_number(&mut self) -> Result<f64, ParseFloatError> {
+    fn at_line_continuation(&mut self) -> bool {
+        match self.chars.peek_nth(1) {
+            Some('\n') => true,
+            Some('\r') => self.chars.peek_nth(2) == Some('\n'),
+            _ => false,
+        }
+    }
+
+    fn word_at(&mut self, offset: usize) -> String {
+        let mut word = String::new();
+        while let Some(ch) = self.chars.peek_nth(offset + word.chars().count()) {
+            if ch.is_alphanumeric() || ch == '_' {
+                word.push(ch);
+            } else {
+                break;
+            }
+        }
+        word
+    }
+
+    fn read_number(&mut self) -> Option<f64> {
         let mut num_str = String::new();
 
         if let Some(&'-') = self.chars.peek() {
@@ -65,16 +268,34 @@ impl<'a> Lexer<'a> {
             self.chars.next();
         }
 
+        let mut has_exponent = false;
         while let Some(&ch) = self.chars.peek() {
-            if ch.is_ascii_digit() || ch == '.' {
+            let next_is_digit = self.chars.peek_nth(1).is_some_and(|c| c.is_ascii_digit());
+            if ch.is_ascii_digit() || ch == '.' || (ch == '_' && next_is_digit) {
+                num_str.push(ch);
+                self.chars.next();
+            } else if matches!(ch, 'e' | 'E') && !has_exponent && self.at_exponent() {
+                has_exponent = true;
                 num_str.push(ch);
                 self.chars.next();
+                if let Some(&sign @ ('+' | '-')) = self.chars.peek() {
+                    num_str.push(sign);
+                    self.chars.next();
+                }
             } else {
                 break;
             }
         }
 
-        num_str.parse()
+        parse_number_str(&num_str)
+    }
+
+    fn at_exponent(&mut self) -> bool {
+        match self.chars.peek_nth(1) {
+            Some(ch) if ch.is_ascii_digit() => true,
+            Some('+' | '-') => self.chars.peek_nth(2).is_some_and(|c| c.is_ascii_digit()),
+            _ => false,
+        }
     }
 
     fn read_identifier(&mut self) -> String {
@@ -93,20 +314,15 @@ impl<'a> Lexer<'a> {
     fn read_string(&mut self) -> String {
         let mut string = String::new();
 
-        if let Some(&ch) = self.chars.peek() {
-            if ch != '"' {
-                return string;
-            }
-            self.chars.next();
-        } else {
+        let Some(quote) = self.chars.next() else {
             return string;
-        }
-
-        while let Some(&ch) = self.chars.peek() {
-            self.chars.next();
+        };
 
-            if ch == '"' {
+        while let Some(ch) = self.chars.next() {
+            if ch == quote {
                 break;
+            } else if ch == '\\' {
+                self.read_escape(&mut string);
             } else {
                 string.push(ch);
             }
@@ -115,22 +331,106 @@ impl<'a> Lexer<'a> {
         string
     }
 
-    fn next_token(&mut self) -> Token {
+    fn read_escape(&mut self, string: &mut String) {
+        let escaped = match self.chars.next() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('0') => '\0',
+            Some(ch @ ('\\' | '"' | '\'')) => ch,
+            Some('x') => match self.read_hex_digits(2) {
+                Some(code) => char::from(code as u8),
+                None => {
+                    string.push_str("\\x");
+                    return;
+                }
+            },
+            Some('u') if self.chars.peek() == Some(&'{') => {
+                self.chars.next();
+                let mut digits = String::new();
+                // Only hex digits are taken, so a malformed escape never
+                // swallows the closing quote.
+                while let Some(&ch) = self.chars.peek() {
+                    if !ch.is_ascii_hexdigit() || digits.len() == 6 {
+                        break;
+                    }
+                    digits.push(ch);
+                    self.chars.next();
+                }
+                let code = u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .and_then(char::from_u32);
+                match (code, self.chars.peek()) {
+                    (Some(ch), Some(&'}')) => {
+                        self.chars.next();
+                        ch
+                    }
+                    _ => {
+                        string.push_str("\\u{");
+                        string.push_str(&digits);
+                        return;
+                    }
+                }
+            }
+            Some(other) => {
+                string.push('\\');
+                other
+            }
+            None => '\\',
+        };
+        string.push(escaped);
+    }
+
+    fn read_hex_digits(&mut self, count: usize) -> Option<u32> {
+        let mut code = 0;
+        for i in 0..count {
+            code = code * 16 + self.chars.peek_nth(i)?.to_digit(16)?;
+        }
+        for _ in 0..count {
+            self.chars.next();
+        }
+        Some(code)
+    }
+
+    fn lex_token(&mut self) -> Token {
+        if let Some(tokens) = &mut self.tokens {
+            return tokens.pop_front().unwrap_or(Token::EOF);
+        }
+
         loop {
             self.skip_whitespace();
 
             match self.chars.peek() {
                 None => {
-                    return Token::EOF;
+                    return match self.read_error.borrow_mut().take() {
+                        Some(message) => Token::Error(message),
+                        None => Token::EOF,
+                    };
                 }
                 Some(&'=') => {
                     self.chars.next();
+                    if let Some(&'=') = self.chars.peek() {
+                        self.chars.next();
+                        return Token::EqualEqual;
+                    }
                     return Token::Equals;
                 }
+                Some(&'!') => {
+                    self.chars.next();
+                    if let Some(&'=') = self.chars.peek() {
+                        self.chars.next();
+                        return Token::NotEqual;
+                    }
+                    continue;
+                }
                 Some(&';') => {
                     self.chars.next();
                     return Token::SemiColon;
                 }
+                Some(&'#') => {
+                    self.skip_line();
+                    continue;
+                }
                 Some(&'(') => {
                     self.chars.next();
                     return Token::LeftParen;
@@ -139,21 +439,36 @@ impl<'a> Lexer<'a> {
                     self.chars.next();
                     return Token::RightParen;
                 }
+                Some(&'{') => {
+                    self.chars.next();
+                    return Token::LeftBrace;
+                }
+                Some(&'}') => {
+                    self.chars.next();
+                    return Token::RightBrace;
+                }
                 Some(&',') => {
                     self.chars.next();
                     return Token::Comma;
                 }
+                Some(&':') => {
+                    self.chars.next();
+                    return Token::Colon;
+                }
                 Some(&'-') => {
-                    let mut chars_clone = self.chars.clone();
-                    chars_clone.next();
-                    if let Some(&ch) = chars_clone.peek()
+                    if self.word_at(1) == "Infinity" {
+                        self.chars.next();
+                        self.read_identifier();
+                        return Token::Literal(Literal::Number(f64::NEG_INFINITY));
+                    }
+                    if let Some(ch) = self.chars.peek_nth(1)
                         && (ch.is_ascii_digit() || ch == '.')
                     {
                         match self.read_number() {
-                            Ok(num) => {
+                            Some(num) => {
                                 return Token::Literal(Literal::Number(num));
                             }
-                            Err(_) => {
+                            None => {
                                 self.chars.next();
                                 return Token::Subtract;
                             }
@@ -163,23 +478,21 @@ impl<'a> Lexer<'a> {
                     return Token::Subtract;
                 }
                 Some(&'.') => {
-                    let mut chars_clone = self.chars.clone();
-                    chars_clone.next();
-                    if let Some(&ch) = chars_clone.peek()
+                    if let Some(ch) = self.chars.peek_nth(1)
                         && (ch.is_ascii_digit() || ch == '.')
                     {
                         match self.read_number() {
-                            Ok(num) => {
+                            Some(num) => {
                                 return Token::Literal(Literal::Number(num));
                             }
-                            Err(_) => {
+                            None => {
                                 self.chars.next();
                                 continue;
                             }
                         }
                     }
                     self.chars.next();
-                    continue;
+                    return Token::Dot;
                 }
                 Some(&'+') => {
                     self.chars.next();
@@ -191,10 +504,57 @@ impl<'a> Lexer<'a> {
                 }
                 Some(&'/') => {
                     self.chars.next();
+                    if let Some(&'/') = self.chars.peek() {
+                        self.skip_line();
+                        continue;
+                    }
+                    if let Some(&'*') = self.chars.peek() {
+                        self.chars.next();
+                        if self.skip_block_comment() {
+                            continue;
+                        }
+                        return Token::Error("unterminated block comment".to_string());
+                    }
                     return Token::Divide;
                 }
+                Some(&'%') => {
+                    self.chars.next();
+                    return Token::Modulo;
+                }
+                Some(&'&') => {
+                    self.chars.next();
+                    return Token::BitAnd;
+                }
+                Some(&'|') => {
+                    self.chars.next();
+                    return Token::BitOr;
+                }
+                Some(&'^') => {
+                    self.chars.next();
+                    if let Some(&'^') = self.chars.peek() {
+                        self.chars.next();
+                        return Token::BitXor;
+                    }
+                    return Token::Error("unexpected '^'".to_string());
+                }
+                Some(&'<') => {
+                    self.chars.next();
+                    if let Some(&'<') = self.chars.peek() {
+                        self.chars.next();
+                        return Token::ShiftLeft;
+                    }
+                    return Token::Error("unexpected '<'".to_string());
+                }
+                Some(&'>') => {
+                    self.chars.next();
+                    if let Some(&'>') = self.chars.peek() {
+                        self.chars.next();
+                        return Token::ShiftRight;
+                    }
+                    return Token::Error("unexpected '>'".to_string());
+                }
                 Some(ch) if ch.is_ascii_digit() => {
-                    if let Ok(num) = self.read_number() {
+                    if let Some(num) = self.read_number() {
                         return Token::Literal(Literal::Number(num));
                     } else {
                         self.chars.next();
@@ -207,18 +567,42 @@ impl<'a> Lexer<'a> {
                         "var" => {
                             return Token::Var;
                         }
+                        "switch" => {
+                            return Token::Switch;
+                        }
+                        "if" => {
+                            return Token::If;
+                        }
+                        "else" => {
+                            return Token::Else;
+                        }
+                        "while" => {
+                            return Token::While;
+                        }
+                        "case" => {
+                            return Token::Case;
+                        }
+                        "default" => {
+                            return Token::Default;
+                        }
                         "true" => {
                             return Token::Literal(Literal::Boolean(true));
                         }
                         "false" => {
                             return Token::Literal(Literal::Boolean(false));
                         }
+                        "Infinity" => {
+                            return Token::Literal(Literal::Number(f64::INFINITY));
+                        }
+                        "NaN" => {
+                            return Token::Literal(Literal::Number(f64::NAN));
+                        }
                         _ => {
                             return Token::Identifier(identifier);
                         }
                     }
                 }
-                Some(&'"') => {
+                Some(&'"') | Some(&'\'') => {
                     let string = self.read_string();
                     return Token::Literal(Literal::String(string));
                 }
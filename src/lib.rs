@@ -1,7 +1,13 @@
+pub mod analysis;
 pub mod interpreter;
 pub mod lexer;
 pub mod parser;
+pub mod unparse;
 
-pub use interpreter::{Interpreter, RuntimeError, Value};
+pub use interpreter::{
+    EvalError, Interpreter, Limits, MAX_INDEX, NativeFn, NumberFormat, RuntimeError, SharedBuffer,
+    StateSnapshot, Value, ValueKind,
+};
 pub use lexer::{Lexer, Literal, Token};
-pub use parser::{BinaryOp, Expression, ParseError, Parser, Program, Statement};
+pub use parser::{BinaryOp, Expression, ParseError, Parser, Program, Statement, precedence_of};
+pub use unparse::unparse;
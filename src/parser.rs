@@ -6,6 +6,14 @@ pub enum BinaryOp {
     Subtract,
     Multiply,
     Divide,
+    Modulo,
+    Equal,
+    NotEqual,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -21,11 +29,38 @@ pub enum Expression {
         op: BinaryOp,
         right: Box<Expression>,
     },
+    Assignment {
+        name: String,
+        value: Box<Expression>,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Statement {
-    VarDeclaration { name: String, value: Expression },
+    VarDeclaration {
+        name: String,
+        value: Expression,
+    },
+    TupleAssignment {
+        names: Vec<String>,
+        values: Vec<Expression>,
+    },
+    /// Runs the first case whose value equals `subject`, otherwise `default`.
+    /// Cases never fall through into the next one.
+    Switch {
+        subject: Expression,
+        cases: Vec<(Expression, Vec<Statement>)>,
+        default: Option<Vec<Statement>>,
+    },
+    If {
+        condition: Expression,
+        then_branch: Vec<Statement>,
+        else_branch: Option<Vec<Statement>>,
+    },
+    While {
+        condition: Expression,
+        body: Vec<Statement>,
+    },
     Expression(Expression),
 }
 
@@ -34,20 +69,100 @@ pub struct Program {
     pub statements: Vec<Statement>,
 }
 
+impl Program {
+    pub fn len(&self) -> usize {
+        self.statements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.statements.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Statement> {
+        self.statements.iter()
+    }
+
+    pub fn count_by(&self, predicate: impl Fn(&Statement) -> bool) -> usize {
+        self.statements
+            .iter()
+            .filter(|stmt| predicate(stmt))
+            .count()
+    }
+}
+
+impl<'p> IntoIterator for &'p Program {
+    type Item = &'p Statement;
+    type IntoIter = std::slice::Iter<'p, Statement>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
 #[derive(Debug)]
 pub enum ParseError {
     UnexpectedToken { expected: String, found: Token },
     UnexpectedEOF,
     InvalidExpression,
+    AssignmentCountMismatch { targets: usize, values: usize },
+    LiteralTooLarge { limit: usize, found: usize },
+}
+
+pub fn precedence_of(op: &BinaryOp) -> u8 {
+    match op {
+        BinaryOp::Equal | BinaryOp::NotEqual => 1,
+        BinaryOp::BitOr => 2,
+        BinaryOp::BitXor => 3,
+        BinaryOp::BitAnd => 4,
+        BinaryOp::ShiftLeft | BinaryOp::ShiftRight => 5,
+        BinaryOp::Add | BinaryOp::Subtract => 6,
+        BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::Modulo => 7,
+    }
 }
 
+fn binary_op(token: &Token) -> Option<BinaryOp> {
+    match token {
+        Token::EqualEqual => Some(BinaryOp::Equal),
+        Token::NotEqual => Some(BinaryOp::NotEqual),
+        Token::BitOr => Some(BinaryOp::BitOr),
+        Token::BitXor => Some(BinaryOp::BitXor),
+        Token::BitAnd => Some(BinaryOp::BitAnd),
+        Token::ShiftLeft => Some(BinaryOp::ShiftLeft),
+        Token::ShiftRight => Some(BinaryOp::ShiftRight),
+        Token::Add => Some(BinaryOp::Add),
+        Token::Subtract => Some(BinaryOp::Subtract),
+        Token::Multiply => Some(BinaryOp::Multiply),
+        Token::Divide => Some(BinaryOp::Divide),
+        Token::Modulo => Some(BinaryOp::Modulo),
+        _ => None,
+    }
+}
+
+const DEFAULT_MAX_LITERAL_LEN: usize = 1_000_000;
+
 pub struct Parser<'a> {
     lexer: Lexer<'a>,
+    max_literal_len: usize,
 }
 
 impl<'a> Parser<'a> {
     pub fn new(lexer: Lexer<'a>) -> Self {
-        Self { lexer }
+        Self {
+            lexer,
+            max_literal_len: DEFAULT_MAX_LITERAL_LEN,
+        }
+    }
+
+    pub fn from_tokens(tokens: Vec<Token>) -> Parser<'static> {
+        Parser::new(Lexer::from_tokens(tokens))
+    }
+
+    /// Caps the length, in characters, of string literals in the parsed
+    /// program. The check runs on tokens the lexer has already built, so it
+    /// bounds the size of the AST rather than memory used while lexing.
+    /// Number literals and identifiers are not checked.
+    pub fn set_max_literal_len(&mut self, limit: usize) {
+        self.max_literal_len = limit;
     }
 
     pub fn parse(&mut self) -> Result<Program, ParseError> {
@@ -61,12 +176,54 @@ impl<'a> Parser<'a> {
         Ok(Program { statements })
     }
 
+    pub fn parse_all(&mut self) -> (Program, Vec<ParseError>) {
+        let mut statements = Vec::new();
+        let mut errors = Vec::new();
+
+        while !matches!(self.lexer.current_token(), Token::EOF) {
+            match self.parse_statement() {
+                Ok(stmt) => statements.push(stmt),
+                Err(error) => {
+                    errors.push(error);
+                    self.synchronize();
+                }
+            }
+        }
+
+        (Program { statements }, errors)
+    }
+
+    fn synchronize(&mut self) {
+        loop {
+            match self.lexer.current_token() {
+                Token::EOF => return,
+                Token::SemiColon => {
+                    self.lexer.advance();
+                    return;
+                }
+                _ => self.lexer.advance(),
+            }
+        }
+    }
+
+    pub fn parse_single_expression(&mut self) -> Result<Expression, ParseError> {
+        let expression = self.parse_expression()?;
+        self.expect_token(Token::EOF)?;
+        Ok(expression)
+    }
+
     fn parse_statement(&mut self) -> Result<Statement, ParseError> {
         match self.lexer.current_token() {
             Token::Var => self.parse_var_declaration(),
+            Token::Switch => self.parse_switch(),
+            Token::If => self.parse_if(),
+            Token::While => self.parse_while(),
+            Token::Identifier(_) if matches!(self.lexer.peek_token(), Token::Comma) => {
+                self.parse_tuple_assignment()
+            }
             _ => {
                 let expr = self.parse_expression()?;
-                self.expect_token(Token::SemiColon)?;
+                self.expect_terminator()?;
                 Ok(Statement::Expression(expr))
             }
         }
@@ -90,50 +247,195 @@ impl<'a> Parser<'a> {
 
         let value = self.parse_expression()?;
 
-        self.expect_token(Token::SemiColon)?;
+        self.expect_terminator()?;
 
         Ok(Statement::VarDeclaration { name, value })
     }
 
-    fn parse_expression(&mut self) -> Result<Expression, ParseError> {
-        self.parse_additive()
+    fn parse_switch(&mut self) -> Result<Statement, ParseError> {
+        self.lexer.advance();
+
+        self.expect_token(Token::LeftParen)?;
+        let subject = self.parse_expression()?;
+        self.expect_token(Token::RightParen)?;
+        self.expect_token(Token::LeftBrace)?;
+
+        let mut cases = Vec::new();
+        let mut default = None;
+
+        loop {
+            match self.lexer.current_token() {
+                Token::Case => {
+                    self.lexer.advance();
+                    let value = self.parse_expression()?;
+                    self.expect_token(Token::Colon)?;
+                    cases.push((value, self.parse_case_body()?));
+                }
+                Token::Default if default.is_none() => {
+                    self.lexer.advance();
+                    self.expect_token(Token::Colon)?;
+                    default = Some(self.parse_case_body()?);
+                }
+                Token::RightBrace => {
+                    self.lexer.advance();
+                    break;
+                }
+                Token::EOF => return Err(ParseError::UnexpectedEOF),
+                token => {
+                    return Err(ParseError::UnexpectedToken {
+                        expected: "'case', 'default' or '}'".to_string(),
+                        found: token.clone(),
+                    });
+                }
+            }
+        }
+
+        Ok(Statement::Switch {
+            subject,
+            cases,
+            default,
+        })
     }
 
-    fn parse_additive(&mut self) -> Result<Expression, ParseError> {
-        let mut left = self.parse_multiplicative()?;
+    fn parse_if(&mut self) -> Result<Statement, ParseError> {
+        self.lexer.advance();
+
+        self.expect_token(Token::LeftParen)?;
+        let condition = self.parse_expression()?;
+        self.expect_token(Token::RightParen)?;
+        let then_branch = self.parse_block()?;
+
+        let else_branch = if matches!(self.lexer.current_token(), Token::Else) {
+            self.lexer.advance();
+            Some(self.parse_block()?)
+        } else {
+            None
+        };
 
+        Ok(Statement::If {
+            condition,
+            then_branch,
+            else_branch,
+        })
+    }
+
+    fn parse_while(&mut self) -> Result<Statement, ParseError> {
+        self.lexer.advance();
+
+        self.expect_token(Token::LeftParen)?;
+        let condition = self.parse_expression()?;
+        self.expect_token(Token::RightParen)?;
+        let body = self.parse_block()?;
+
+        Ok(Statement::While { condition, body })
+    }
+
+    fn parse_block(&mut self) -> Result<Vec<Statement>, ParseError> {
+        self.expect_token(Token::LeftBrace)?;
+
+        let mut body = Vec::new();
         loop {
-            let op = match self.lexer.current_token() {
-                Token::Add => BinaryOp::Add,
-                Token::Subtract => BinaryOp::Subtract,
-                _ => break,
-            };
+            match self.lexer.current_token() {
+                Token::RightBrace => {
+                    self.lexer.advance();
+                    return Ok(body);
+                }
+                Token::EOF => return Err(ParseError::UnexpectedEOF),
+                _ => body.push(self.parse_statement()?),
+            }
+        }
+    }
+
+    fn parse_case_body(&mut self) -> Result<Vec<Statement>, ParseError> {
+        let mut body = Vec::new();
+
+        while !matches!(
+            self.lexer.current_token(),
+            Token::Case | Token::Default | Token::RightBrace | Token::EOF
+        ) {
+            body.push(self.parse_statement()?);
+        }
+
+        Ok(body)
+    }
+
+    fn parse_tuple_assignment(&mut self) -> Result<Statement, ParseError> {
+        let mut names = Vec::new();
 
+        loop {
+            match self.lexer.current_token() {
+                Token::Identifier(name) => names.push(name.clone()),
+                token => {
+                    return Err(ParseError::UnexpectedToken {
+                        expected: "identifier".to_string(),
+                        found: token.clone(),
+                    });
+                }
+            }
             self.lexer.advance();
-            let right = self.parse_multiplicative()?;
 
-            left = Expression::Binary {
-                left: Box::new(left),
-                op,
-                right: Box::new(right),
-            };
+            if matches!(self.lexer.current_token(), Token::Comma) {
+                self.lexer.advance();
+            } else {
+                break;
+            }
         }
 
-        Ok(left)
+        self.expect_token(Token::Equals)?;
+
+        let mut values = vec![self.parse_expression()?];
+        while matches!(self.lexer.current_token(), Token::Comma) {
+            self.lexer.advance();
+            values.push(self.parse_expression()?);
+        }
+
+        self.expect_terminator()?;
+
+        if names.len() != values.len() {
+            return Err(ParseError::AssignmentCountMismatch {
+                targets: names.len(),
+                values: values.len(),
+            });
+        }
+
+        Ok(Statement::TupleAssignment { names, values })
+    }
+
+    fn parse_expression(&mut self) -> Result<Expression, ParseError> {
+        self.parse_assignment()
+    }
+
+    fn parse_assignment(&mut self) -> Result<Expression, ParseError> {
+        let target = self.parse_binary(0)?;
+
+        if !matches!(self.lexer.current_token(), Token::Equals) {
+            return Ok(target);
+        }
+
+        let Expression::Identifier(name) = target else {
+            return Err(ParseError::InvalidExpression);
+        };
+
+        self.lexer.advance();
+        let value = self.parse_assignment()?;
+
+        Ok(Expression::Assignment {
+            name,
+            value: Box::new(value),
+        })
     }
 
-    fn parse_multiplicative(&mut self) -> Result<Expression, ParseError> {
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Expression, ParseError> {
         let mut left = self.parse_primary()?;
 
-        loop {
-            let op = match self.lexer.current_token() {
-                Token::Multiply => BinaryOp::Multiply,
-                Token::Divide => BinaryOp::Divide,
-                _ => break,
-            };
+        while let Some(op) = binary_op(self.lexer.current_token()) {
+            let precedence = precedence_of(&op);
+            if precedence <= min_precedence {
+                break;
+            }
 
             self.lexer.advance();
-            let right = self.parse_primary()?;
+            let right = self.parse_binary(precedence)?;
 
             left = Expression::Binary {
                 left: Box::new(left),
@@ -148,6 +450,15 @@ impl<'a> Parser<'a> {
     fn parse_primary(&mut self) -> Result<Expression, ParseError> {
         match self.lexer.current_token() {
             Token::Literal(literal) => {
+                if let Literal::String(text) = literal {
+                    let len = text.chars().count();
+                    if len > self.max_literal_len {
+                        return Err(ParseError::LiteralTooLarge {
+                            limit: self.max_literal_len,
+                            found: len,
+                        });
+                    }
+                }
                 let expr = Expression::Literal(literal.clone());
                 self.lexer.advance();
                 Ok(expr)
@@ -156,6 +467,19 @@ impl<'a> Parser<'a> {
                 let name = name.clone();
                 self.lexer.advance();
 
+                if matches!(self.lexer.current_token(), Token::Dot) {
+                    self.lexer.advance();
+                    let Token::Identifier(member) = self.lexer.current_token() else {
+                        return Err(ParseError::UnexpectedToken {
+                            expected: "identifier".to_string(),
+                            found: self.lexer.current_token().clone(),
+                        });
+                    };
+                    let name = format!("{name}.{member}");
+                    self.lexer.advance();
+                    return self.parse_function_call(name);
+                }
+
                 if matches!(self.lexer.current_token(), Token::LeftParen) {
                     self.parse_function_call(name)
                 } else {
@@ -210,6 +534,15 @@ impl<'a> Parser<'a> {
         Ok(Expression::FunctionCall { name, args })
     }
 
+    // The semicolon after a statement may be left off when the statement is
+    // the last one in the program or in a `{ ... }` block.
+    fn expect_terminator(&mut self) -> Result<(), ParseError> {
+        if matches!(self.lexer.current_token(), Token::EOF | Token::RightBrace) {
+            return Ok(());
+        }
+        self.expect_token(Token::SemiColon)
+    }
+
     fn expect_token(&mut self, expected: Token) -> Result<(), ParseError> {
         if std::mem::discriminant(self.lexer.current_token()) == std::mem::discriminant(&expected) {
             self.lexer.advance();
@@ -0,0 +1,178 @@
+use crate::lexer::Literal;
+use crate::parser::{BinaryOp, Expression, Program, Statement, precedence_of};
+
+const INDENT: &str = "    ";
+
+pub fn unparse(program: &Program) -> String {
+    let mut out = String::new();
+    for statement in &program.statements {
+        write_statement(&mut out, statement, 0);
+    }
+    out
+}
+
+pub fn unparse_statement(statement: &Statement) -> String {
+    let mut out = String::new();
+    write_statement(&mut out, statement, 0);
+    out
+}
+
+pub fn unparse_expression(expression: &Expression) -> String {
+    match expression {
+        Expression::Literal(literal) => unparse_literal(literal),
+        Expression::Identifier(name) => name.clone(),
+        Expression::FunctionCall { name, args } => {
+            let args: Vec<String> = args.iter().map(unparse_expression).collect();
+            format!("{name}({})", args.join(", "))
+        }
+        Expression::Assignment { name, value } => {
+            format!("{name} = {}", unparse_expression(value))
+        }
+        Expression::Binary { left, op, right } => {
+            let precedence = precedence_of(op);
+            format!(
+                "{} {} {}",
+                unparse_operand(left, precedence, false),
+                operator_symbol(op),
+                unparse_operand(right, precedence, true)
+            )
+        }
+    }
+}
+
+fn write_statement(out: &mut String, statement: &Statement, depth: usize) {
+    let pad = INDENT.repeat(depth);
+
+    match statement {
+        Statement::VarDeclaration { name, value } => {
+            out.push_str(&format!(
+                "{pad}var {name} = {};\n",
+                unparse_expression(value)
+            ));
+        }
+        Statement::TupleAssignment { names, values } => {
+            let values: Vec<String> = values.iter().map(unparse_expression).collect();
+            out.push_str(&format!(
+                "{pad}{} = {};\n",
+                names.join(", "),
+                values.join(", ")
+            ));
+        }
+        Statement::Switch {
+            subject,
+            cases,
+            default,
+        } => {
+            out.push_str(&format!(
+                "{pad}switch ({}) {{\n",
+                unparse_expression(subject)
+            ));
+            for (value, body) in cases {
+                out.push_str(&format!(
+                    "{pad}{INDENT}case {}:\n",
+                    unparse_expression(value)
+                ));
+                for statement in body {
+                    write_statement(out, statement, depth + 2);
+                }
+            }
+            if let Some(body) = default {
+                out.push_str(&format!("{pad}{INDENT}default:\n"));
+                for statement in body {
+                    write_statement(out, statement, depth + 2);
+                }
+            }
+            out.push_str(&format!("{pad}}}\n"));
+        }
+        Statement::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            out.push_str(&format!("{pad}if ({}) {{\n", unparse_expression(condition)));
+            for statement in then_branch {
+                write_statement(out, statement, depth + 1);
+            }
+            if let Some(body) = else_branch {
+                out.push_str(&format!("{pad}}} else {{\n"));
+                for statement in body {
+                    write_statement(out, statement, depth + 1);
+                }
+            }
+            out.push_str(&format!("{pad}}}\n"));
+        }
+        Statement::While { condition, body } => {
+            out.push_str(&format!(
+                "{pad}while ({}) {{\n",
+                unparse_expression(condition)
+            ));
+            for statement in body {
+                write_statement(out, statement, depth + 1);
+            }
+            out.push_str(&format!("{pad}}}\n"));
+        }
+        Statement::Expression(expr) => {
+            out.push_str(&format!("{pad}{};\n", unparse_expression(expr)));
+        }
+    }
+}
+
+fn unparse_operand(operand: &Expression, parent_precedence: u8, is_right: bool) -> String {
+    let text = unparse_expression(operand);
+
+    match operand {
+        Expression::Assignment { .. } => format!("({text})"),
+        Expression::Binary { op, .. }
+            if precedence_of(op) < parent_precedence
+                || (is_right && precedence_of(op) == parent_precedence) =>
+        {
+            format!("({text})")
+        }
+        _ => text,
+    }
+}
+
+fn unparse_literal(literal: &Literal) -> String {
+    match literal {
+        Literal::String(s) => format!("\"{}\"", escape_string(s)),
+        Literal::Number(n) if n.is_nan() => "NaN".to_string(),
+        Literal::Number(n) if n.is_infinite() => {
+            if *n > 0.0 { "Infinity" } else { "-Infinity" }.to_string()
+        }
+        Literal::Number(n) => n.to_string(),
+        Literal::Boolean(b) => b.to_string(),
+    }
+}
+
+fn escape_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for ch in s.chars() {
+        match ch {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\0' => escaped.push_str("\\0"),
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+fn operator_symbol(op: &BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Add => "+",
+        BinaryOp::Subtract => "-",
+        BinaryOp::Multiply => "*",
+        BinaryOp::Divide => "/",
+        BinaryOp::Modulo => "%",
+        BinaryOp::Equal => "==",
+        BinaryOp::NotEqual => "!=",
+        BinaryOp::BitAnd => "&",
+        BinaryOp::BitOr => "|",
+        BinaryOp::BitXor => "^^",
+        BinaryOp::ShiftLeft => "<<",
+        BinaryOp::ShiftRight => ">>",
+    }
+}
@@ -0,0 +1,64 @@
+use simple_script_compiler::analysis::{DiagnosticKind, check, check_with, lint};
+use simple_script_compiler::lexer::Lexer;
+use simple_script_compiler::parser::{Parser, Program};
+
+fn parse(src: &str) -> Program {
+    Parser::new(Lexer::new(src)).parse().expect("should parse")
+}
+
+#[test]
+fn lint_flags_unused_pure_expressions() {
+    let diagnostics = lint(&parse("1 + 2;"));
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].kind, DiagnosticKind::UnusedExpression);
+    assert!(diagnostics[0].message.contains("1 + 2"));
+
+    assert!(lint(&parse("println(1);")).is_empty());
+    assert!(lint(&parse("var a = 1; a = 2; var b = a + 1;")).is_empty());
+    assert!(lint(&parse("var a = 1; 1 + typeof(a);")).is_empty());
+
+    let diagnostics = lint(&parse("switch (1) { case 1: a; default: println(2); }"));
+    assert_eq!(diagnostics.len(), 1);
+}
+
+#[test]
+fn check_reports_undeclared_variables() {
+    let diagnostics = check(&parse(
+        r#"var a = 1; println(a + typo); b = 2; var b = a; switch (a) { case 1: var c = b; } c = c + 1;"#,
+    ));
+    let found: Vec<_> = diagnostics
+        .iter()
+        .map(|d| (d.kind.clone(), d.message.as_str()))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            (
+                DiagnosticKind::UndefinedVariable,
+                "'typo' is never declared"
+            ),
+            (
+                DiagnosticKind::UseBeforeDeclaration,
+                "'b' is used before it is declared"
+            ),
+        ]
+    );
+
+    assert!(
+        check(&parse(
+            "var x = 1; var y = x * 2; x, y = y, x; print(x, y);"
+        ))
+        .is_empty()
+    );
+}
+
+#[test]
+fn check_with_accepts_host_defined_names() {
+    let program = parse("var total = price * qty; println(total, missing);");
+    assert_eq!(check(&program).len(), 3);
+
+    let diagnostics = check_with(&program, &["price", "qty"]);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].kind, DiagnosticKind::UndefinedVariable);
+    assert_eq!(diagnostics[0].message, "'missing' is never declared");
+}
@@ -154,6 +154,7 @@ fn interpreter_strings_and_concatenation_and_typeof() {
 }
 
 #[test]
+#[allow(clippy::approx_constant)]
 fn interpreter_negative_and_decimal() {
     let src = r#"
 	var negative = -15;
@@ -1,4 +1,4 @@
-use simple_script_compiler::interpreter::{Interpreter, Value};
+use simple_script_compiler::interpreter::{Interpreter, RuntimeError, SharedBuffer, Value};
 use simple_script_compiler::lexer::Lexer;
 use simple_script_compiler::parser::Parser;
 
@@ -18,6 +18,10 @@ fn run_program(source: &str) -> Result<Interpreter, String> {
     Ok(interpreter)
 }
 
+fn eval_expr(source: &str) -> Result<Value, RuntimeError> {
+    Interpreter::new().evaluate(Parser::new(Lexer::new(source)).parse().unwrap())
+}
+
 #[test]
 fn interpreter_arithmetic_and_precedence() {
     let src = r#"
@@ -56,3 +60,1580 @@ fn interpreter_strings_and_concatenation_and_typeof() {
         &Value::String("string".to_string())
     );
 }
+
+#[test]
+fn number_equality_uses_epsilon_when_set() {
+    let src = r#"
+    var exact = 0.1 + 0.2 == 0.3;
+    var differs = 0.1 + 0.2 != 0.3;
+    var inf_equal = Infinity == Infinity;
+    var neg_inf_equal = -Infinity == -Infinity;
+    var inf_differs = Infinity != Infinity;
+    "#;
+
+    let interp = run_program(src).expect("should run");
+    let vars = interp.get_variables();
+    assert_eq!(vars.get("exact").unwrap(), &Value::Boolean(false));
+    assert_eq!(vars.get("differs").unwrap(), &Value::Boolean(true));
+
+    let lexer = Lexer::new(src);
+    let mut parser = Parser::new(lexer);
+    let program = parser.parse().expect("should parse");
+    let mut interp = Interpreter::new();
+    interp.set_number_epsilon(1e-9);
+    interp.interpret(program).expect("should run");
+    let vars = interp.get_variables();
+    assert_eq!(vars.get("exact").unwrap(), &Value::Boolean(true));
+    assert_eq!(vars.get("differs").unwrap(), &Value::Boolean(false));
+    assert_eq!(vars.get("inf_equal").unwrap(), &Value::Boolean(true));
+    assert_eq!(vars.get("neg_inf_equal").unwrap(), &Value::Boolean(true));
+    assert_eq!(vars.get("inf_differs").unwrap(), &Value::Boolean(false));
+}
+
+#[cfg(feature = "fs")]
+#[test]
+fn read_and_write_file_stay_inside_fs_root() {
+    let root = std::env::temp_dir().join(format!("ssc_fs_test_{}", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+
+    let src = r#"
+    write_file("note.txt", "hello file");
+    var contents = read_file("note.txt");
+    "#;
+    let lexer = Lexer::new(src);
+    let mut parser = Parser::new(lexer);
+    let program = parser.parse().expect("should parse");
+    let mut interp = Interpreter::new();
+    interp.set_fs_root(&root);
+    interp.interpret(program).expect("should run");
+    assert_eq!(
+        interp.get_variables().get("contents").unwrap(),
+        &Value::String("hello file".to_string())
+    );
+
+    let lexer = Lexer::new(r#"var x = read_file("../outside.txt");"#);
+    let mut parser = Parser::new(lexer);
+    let program = parser.parse().expect("should parse");
+    let mut interp = Interpreter::new();
+    interp.set_fs_root(&root);
+    match interp.interpret(program) {
+        Err(RuntimeError::PermissionDenied(_)) => {}
+        other => panic!("expected PermissionDenied, got {:?}", other),
+    }
+
+    let lexer = Lexer::new(r#"var x = read_file("note.txt");"#);
+    let mut parser = Parser::new(lexer);
+    let program = parser.parse().expect("should parse");
+    let mut interp = Interpreter::new();
+    match interp.interpret(program) {
+        Err(RuntimeError::PermissionDenied(_)) => {}
+        other => panic!("expected PermissionDenied without a root, got {:?}", other),
+    }
+
+    let lexer = Lexer::new(r#"var x = read_file("missing.txt");"#);
+    let mut parser = Parser::new(lexer);
+    let program = parser.parse().expect("should parse");
+    let mut interp = Interpreter::new();
+    interp.set_fs_root(&root);
+    match interp.interpret(program) {
+        Err(RuntimeError::IoError(_)) => {}
+        other => panic!("expected IoError, got {:?}", other),
+    }
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[cfg(all(unix, feature = "fs"))]
+#[test]
+fn symlinks_cannot_escape_fs_root() {
+    let base = std::env::temp_dir().join(format!("ssc_symlink_test_{}", std::process::id()));
+    let root = base.join("root");
+    std::fs::create_dir_all(&root).unwrap();
+    let secret = base.join("secret.txt");
+    std::fs::write(&secret, "SECRET").unwrap();
+    std::os::unix::fs::symlink(&secret, root.join("link.txt")).unwrap();
+    std::os::unix::fs::symlink(base.join("missing.txt"), root.join("dangling.txt")).unwrap();
+
+    for src in [
+        r#"var x = read_file("link.txt");"#,
+        r#"write_file("link.txt", "overwritten");"#,
+        r#"write_file("dangling.txt", "created");"#,
+    ] {
+        let program = Parser::new(Lexer::new(src)).parse().expect("should parse");
+        let mut interp = Interpreter::new();
+        interp.set_fs_root(&root);
+        match interp.interpret(program) {
+            Err(RuntimeError::PermissionDenied(_)) => {}
+            other => panic!("expected PermissionDenied for {src}, got {:?}", other),
+        }
+    }
+    assert_eq!(std::fs::read_to_string(&secret).unwrap(), "SECRET");
+    assert!(!base.join("missing.txt").exists());
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn env_reads_only_allowed_variables() {
+    let src = r#"
+    var home = env("HOME");
+    var missing = env("SIMPLE_SCRIPT_UNSET_VARIABLE");
+    "#;
+
+    let lexer = Lexer::new(src);
+    let mut parser = Parser::new(lexer);
+    let program = parser.parse().expect("should parse");
+    let mut interp = Interpreter::new();
+    match interp.interpret(program.clone()) {
+        Err(RuntimeError::PermissionDenied(_)) => {}
+        other => panic!("expected PermissionDenied, got {:?}", other),
+    }
+
+    let mut interp = Interpreter::new();
+    interp.allow_env_var("HOME");
+    interp.allow_env_var("SIMPLE_SCRIPT_UNSET_VARIABLE");
+    interp.interpret(program).expect("should run");
+    let vars = interp.get_variables();
+    let expected_home = std::env::var("HOME")
+        .map(Value::String)
+        .unwrap_or(Value::Null);
+    assert_eq!(vars.get("home").unwrap(), &expected_home);
+    assert_eq!(vars.get("missing").unwrap(), &Value::Null);
+}
+
+#[test]
+fn infinity_and_nan_literals() {
+    let src = r#"
+    var inf = Infinity;
+    var neg_inf = -Infinity;
+    var nan_type = typeof(NaN);
+    var nan_equal = NaN == NaN;
+    var inf_equal = Infinity == Infinity;
+    "#;
+
+    let interp = run_program(src).expect("should run");
+    let vars = interp.get_variables();
+    match vars.get("inf").unwrap() {
+        Value::Number(n) => assert!(*n > 1e308),
+        other => panic!("inf not number: {:?}", other),
+    }
+    assert_eq!(
+        vars.get("neg_inf").unwrap(),
+        &Value::Number(f64::NEG_INFINITY)
+    );
+    assert_eq!(
+        vars.get("nan_type").unwrap(),
+        &Value::String("number".to_string())
+    );
+    assert_eq!(vars.get("nan_equal").unwrap(), &Value::Boolean(false));
+    assert_eq!(vars.get("inf_equal").unwrap(), &Value::Boolean(true));
+
+    assert_eq!(Value::Number(f64::INFINITY).to_string(), "Infinity");
+    assert_eq!(Value::Number(f64::NEG_INFINITY).to_string(), "-Infinity");
+    assert_eq!(Value::Number(f64::NAN).to_string(), "NaN");
+}
+
+#[test]
+fn run_str_returns_output_and_last_value() {
+    let (output, value) = Interpreter::run_str(r#"println("hi"); 40 + 2"#).expect("should run");
+    assert_eq!(output, "hi\n");
+    assert_eq!(value, Value::Number(42.0));
+
+    let (output, value) = Interpreter::run_str("var x = 1;").expect("should run");
+    assert_eq!(output, "");
+    assert_eq!(value, Value::Null);
+
+    assert!(Interpreter::run_str("var x = y;").is_err());
+}
+
+#[test]
+fn run_source_uses_seeded_variables() {
+    let mut interp = Interpreter::with_variables(
+        [("name".to_string(), Value::String("host".to_string()))]
+            .into_iter()
+            .collect(),
+    );
+    let stdout = SharedBuffer::default();
+    interp.set_output(stdout.clone());
+
+    let (output, value) = interp
+        .run_source(r#"print("hi " + name); var seen = true; 7"#)
+        .expect("should run");
+    assert_eq!(output, "hi host");
+    assert_eq!(value, Value::Number(7.0));
+    assert_eq!(
+        interp.get_variables().get("seen").unwrap(),
+        &Value::Boolean(true)
+    );
+
+    interp
+        .interpret(Parser::new(Lexer::new("print(1);")).parse().unwrap())
+        .unwrap();
+    assert_eq!(stdout.contents(), "1");
+}
+
+#[test]
+fn try_num_returns_null_instead_of_erroring() {
+    let src = r#"
+    var parsed = try_num("12");
+    var padded = try_num(" 2.5 ");
+    var bad = try_num("abc");
+    var flag = try_num(true);
+    var grouped = try_num("1_000");
+    var word = try_num("inf");
+    "#;
+
+    let interp = run_program(src).expect("should run");
+    let vars = interp.get_variables();
+    assert_eq!(vars.get("parsed").unwrap(), &Value::Number(12.0));
+    assert_eq!(vars.get("padded").unwrap(), &Value::Number(2.5));
+    assert_eq!(vars.get("bad").unwrap(), &Value::Null);
+    assert_eq!(vars.get("flag").unwrap(), &Value::Null);
+    assert_eq!(vars.get("grouped").unwrap(), &Value::Number(1000.0));
+    assert_eq!(vars.get("word").unwrap(), &Value::Null);
+}
+
+#[test]
+fn bytes_type_name_and_display() {
+    let bytes = Value::Bytes(vec![0x01, 0xab, 0xff]);
+    assert_eq!(bytes.to_string(), "<bytes 01abff>");
+}
+
+#[cfg(feature = "base64")]
+#[test]
+fn base64_round_trips_bytes() {
+    let src = r#"
+    var raw = from_base64("AAEC/w==");
+    var kind = typeof(raw);
+    var encoded = to_base64(raw);
+    var short = to_base64(from_base64("YQ=="));
+    "#;
+
+    let interp = run_program(src).expect("should run");
+    let vars = interp.get_variables();
+    assert_eq!(
+        vars.get("raw").unwrap(),
+        &Value::Bytes(vec![0x00, 0x01, 0x02, 0xff])
+    );
+    assert_eq!(
+        vars.get("kind").unwrap(),
+        &Value::String("bytes".to_string())
+    );
+    assert_eq!(
+        vars.get("encoded").unwrap(),
+        &Value::String("AAEC/w==".to_string())
+    );
+    assert_eq!(
+        vars.get("short").unwrap(),
+        &Value::String("YQ==".to_string())
+    );
+
+    assert!(run_program(r#"var bad = from_base64("not base64!");"#).is_err());
+}
+
+#[test]
+fn case_insensitive_identifiers_are_opt_in() {
+    let src = r#"
+    var X = 1;
+    var copy = x;
+    PrintLn(copy);
+    "#;
+
+    assert!(run_program(src).is_err());
+
+    let lexer = Lexer::new(src);
+    let mut parser = Parser::new(lexer);
+    let program = parser.parse().expect("should parse");
+    let mut interp = Interpreter::new();
+    interp.set_case_insensitive(true);
+    interp.set_output(std::io::sink());
+    interp.interpret(program).expect("should run");
+    assert_eq!(
+        interp.get_variables().get("copy").unwrap(),
+        &Value::Number(1.0)
+    );
+}
+
+#[test]
+fn case_insensitivity_applies_to_names_defined_earlier() {
+    fn square(args: Vec<Value>) -> Result<Value, RuntimeError> {
+        let n = args[0].as_number()?;
+        Ok(Value::Number(n * n))
+    }
+
+    let mut interp = Interpreter::with_variables(
+        [("MyVar".to_string(), Value::Number(3.0))]
+            .into_iter()
+            .collect(),
+    );
+    interp.register_function("Square", square);
+    interp.define_constant("LIMIT", Value::Number(1.0));
+    interp.set_case_insensitive(true);
+
+    let program = Parser::new(Lexer::new("var total = square(myvar) + limit;"))
+        .parse()
+        .expect("should parse");
+    interp.interpret(program).expect("should run");
+    assert_eq!(
+        interp.get_variables().get("total").unwrap(),
+        &Value::Number(10.0)
+    );
+
+    let program = Parser::new(Lexer::new("Limit = 2;"))
+        .parse()
+        .expect("should parse");
+    assert!(interp.interpret(program).is_err());
+}
+
+#[test]
+fn bitwise_operators_on_integers() {
+    let src = r#"
+    var and = 6 & 3;
+    var or = 6 | 3;
+    var xor = 6 ^^ 3;
+    var left = 1 << 4;
+    var right = 256 >> 4;
+    var precedence = 1 | 2 & 3;
+    var shifted_sum = 1 + 1 << 2;
+    var masked = 6 & 3 == 2;
+    "#;
+
+    let interp = run_program(src).expect("should run");
+    let vars = interp.get_variables();
+    assert_eq!(vars.get("and").unwrap(), &Value::Number(2.0));
+    assert_eq!(vars.get("or").unwrap(), &Value::Number(7.0));
+    assert_eq!(vars.get("xor").unwrap(), &Value::Number(5.0));
+    assert_eq!(vars.get("left").unwrap(), &Value::Number(16.0));
+    assert_eq!(vars.get("right").unwrap(), &Value::Number(16.0));
+    assert_eq!(vars.get("precedence").unwrap(), &Value::Number(3.0));
+    assert_eq!(vars.get("shifted_sum").unwrap(), &Value::Number(8.0));
+    assert_eq!(vars.get("masked").unwrap(), &Value::Boolean(true));
+
+    assert!(run_program("var bad = 1.5 & 1;").is_err());
+    assert!(run_program("var bad = 1 << 64;").is_err());
+
+    match eval_expr("9223372036854775807 & 1") {
+        Err(RuntimeError::TypeError(_)) => {}
+        other => panic!("expected TypeError for 2^63, got {:?}", other),
+    }
+    assert_eq!(
+        eval_expr("-9223372036854775808 & -1").unwrap(),
+        Value::Number(-9223372036854775808.0)
+    );
+    for src in ["1 << 63", "4611686018427387904 << 1"] {
+        match eval_expr(src) {
+            Err(RuntimeError::Overflow) => {}
+            other => panic!("expected Overflow for {src}, got {:?}", other),
+        }
+    }
+    assert_eq!(
+        eval_expr("1 << 62").unwrap(),
+        Value::Number(4611686018427387904.0)
+    );
+    assert_eq!(
+        eval_expr("-1 << 63").unwrap(),
+        Value::Number(-9223372036854775808.0)
+    );
+    assert!(run_program(r#"var bad = "a" | 1;"#).is_err());
+}
+
+#[test]
+fn tuple_assignment_swaps_without_temporary() {
+    let src = r#"
+    var a = 1;
+    var b = 2;
+    a, b = b, a;
+    var a_is_two = a == 2;
+    var b_is_one = b == 1;
+    "#;
+
+    let interp = run_program(src).expect("should run");
+    let vars = interp.get_variables();
+    assert_eq!(vars.get("a").unwrap(), &Value::Number(2.0));
+    assert_eq!(vars.get("b").unwrap(), &Value::Number(1.0));
+    assert_eq!(vars.get("a_is_two").unwrap(), &Value::Boolean(true));
+    assert_eq!(vars.get("b_is_one").unwrap(), &Value::Boolean(true));
+
+    assert!(run_program("var a = 1; a, c = 2, 3;").is_err());
+
+    let mut interp = Interpreter::new();
+    interp.define_constant("K", Value::Number(0.0));
+    for src in ["a, zz = 5, 6;", "a, K = 5, 6;"] {
+        let setup = Parser::new(Lexer::new("var a = 1;")).parse().unwrap();
+        interp.interpret(setup).unwrap();
+        let program = Parser::new(Lexer::new(src)).parse().unwrap();
+        assert!(interp.interpret(program).is_err());
+        assert_eq!(
+            interp.get_variables().get("a").unwrap(),
+            &Value::Number(1.0)
+        );
+        assert!(!interp.get_variables().contains_key("zz"));
+    }
+}
+
+#[test]
+fn on_define_reports_each_binding() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let seen = Rc::new(RefCell::new(Vec::new()));
+    let mut interp = Interpreter::new();
+    let log = Rc::clone(&seen);
+    interp.on_define(move |name, value| log.borrow_mut().push((name.to_string(), value.clone())));
+
+    let src = r#"
+    var a = 1;
+    var b = "two";
+    a, b = b, a;
+    "#;
+    let program = Parser::new(Lexer::new(src)).parse().expect("should parse");
+    interp.interpret(program).expect("should run");
+
+    assert_eq!(
+        *seen.borrow(),
+        vec![
+            ("a".to_string(), Value::Number(1.0)),
+            ("b".to_string(), Value::String("two".to_string())),
+            ("a".to_string(), Value::String("two".to_string())),
+            ("b".to_string(), Value::Number(1.0)),
+        ]
+    );
+}
+
+#[test]
+fn switch_runs_matching_case_or_default() {
+    let src = r#"
+    var kind = "none";
+    var count = 0;
+    switch (1 + 1) {
+        case 1:
+            var kind = "one";
+        case 2:
+            var kind = "two";
+            var count = count + 1;
+        case 2:
+            var count = count + 10;
+        default:
+            var kind = "other";
+    }
+    "#;
+    let interp = run_program(src).expect("should run");
+    let vars = interp.get_variables();
+    assert_eq!(vars.get("kind").unwrap(), &Value::String("two".to_string()));
+    assert_eq!(vars.get("count").unwrap(), &Value::Number(1.0));
+
+    let src = r#"
+    var kind = "none";
+    switch ("z") {
+        case "a": var kind = "a";
+        default: var kind = "fallback";
+    }
+    "#;
+    let interp = run_program(src).expect("should run");
+    assert_eq!(
+        interp.get_variables().get("kind").unwrap(),
+        &Value::String("fallback".to_string())
+    );
+
+    let src = r#"
+    var kind = "none";
+    switch (true) {
+        case false: var kind = "false";
+    }
+    "#;
+    let interp = run_program(src).expect("should run");
+    assert_eq!(
+        interp.get_variables().get("kind").unwrap(),
+        &Value::String("none".to_string())
+    );
+}
+
+#[test]
+fn compare_orders_numbers_and_strings() {
+    let src = r#"
+    var lt = compare(2, 5);
+    var eq = compare(3, 3);
+    var gt = compare("b", "a");
+    var prefix = compare("ab", "abc");
+    "#;
+
+    let interp = run_program(src).expect("should run");
+    let vars = interp.get_variables();
+    assert_eq!(vars.get("lt").unwrap(), &Value::Number(-1.0));
+    assert_eq!(vars.get("eq").unwrap(), &Value::Number(0.0));
+    assert_eq!(vars.get("gt").unwrap(), &Value::Number(1.0));
+    assert_eq!(vars.get("prefix").unwrap(), &Value::Number(-1.0));
+
+    assert!(run_program(r#"var bad = compare(1, "1");"#).is_err());
+    assert!(run_program("var bad = compare(NaN, 1);").is_err());
+}
+
+#[test]
+fn print_sep_joins_with_custom_separator() {
+    let (output, _) = Interpreter::run_str(
+        r#"println_sep("-", 1, 2, 3); print_sep(", ", "a", "b"); println_sep("|");"#,
+    )
+    .expect("should run");
+    assert_eq!(output, "1-2-3\na, b\n");
+
+    assert!(Interpreter::run_str("println_sep();").is_err());
+    assert!(Interpreter::run_str("println_sep(1, 2);").is_err());
+}
+
+#[test]
+fn with_limits_rejects_heavy_scripts() {
+    use simple_script_compiler::interpreter::Limits;
+
+    let heavy = "var a = 1; var b = a + a; var c = b + b; var d = c + c;";
+    let program = Parser::new(Lexer::new(heavy))
+        .parse()
+        .expect("should parse");
+
+    let mut interp = Interpreter::with_limits(Limits::default());
+    interp
+        .interpret(program.clone())
+        .expect("unbounded by default");
+
+    let mut interp = Interpreter::with_limits(Limits {
+        max_steps: Some(5),
+        ..Limits::default()
+    });
+    match interp.interpret(program) {
+        Err(RuntimeError::StepLimitExceeded(limit)) => assert_eq!(limit, 5),
+        other => panic!("expected StepLimitExceeded, got {:?}", other),
+    }
+
+    let nested = "var n = 1 + (2 + (3 + (4 + 5)));";
+    let program = Parser::new(Lexer::new(nested))
+        .parse()
+        .expect("should parse");
+    let mut interp = Interpreter::with_limits(Limits {
+        max_depth: Some(3),
+        ..Limits::default()
+    });
+    match interp.interpret(program) {
+        Err(RuntimeError::DepthLimitExceeded(limit)) => assert_eq!(limit, 3),
+        other => panic!("expected DepthLimitExceeded, got {:?}", other),
+    }
+}
+
+#[test]
+fn assert_approx_passes_within_epsilon() {
+    run_program("assert_approx(0.1 + 0.2, 0.3, 0.000000001);").expect("should pass");
+
+    let program = Parser::new(Lexer::new("assert_approx(1.5, 1, 0.1);"))
+        .parse()
+        .expect("should parse");
+    match Interpreter::new().interpret(program) {
+        Err(RuntimeError::AssertionFailed(msg)) => {
+            assert!(msg.contains("1.5") && msg.contains('1'), "message: {msg}");
+        }
+        other => panic!("expected AssertionFailed, got {:?}", other),
+    }
+
+    let program = Parser::new(Lexer::new(r#"assert_approx("1", 1, 0.1);"#))
+        .parse()
+        .expect("should parse");
+    match Interpreter::new().interpret(program) {
+        Err(RuntimeError::TypeError(_)) => {}
+        other => panic!("expected TypeError, got {:?}", other),
+    }
+}
+
+#[test]
+fn strict_eq_distinguishes_signed_zero() {
+    let src = r#"
+    var loose = 0.0 == -0.0;
+    var strict = strict_eq(0.0, -0.0);
+    var same = strict_eq(1.5, 1.5);
+    var strings = strict_eq("a", "a");
+    var mixed = strict_eq(1, "1");
+    "#;
+
+    let interp = run_program(src).expect("should run");
+    let vars = interp.get_variables();
+    assert_eq!(vars.get("loose").unwrap(), &Value::Boolean(true));
+    assert_eq!(vars.get("strict").unwrap(), &Value::Boolean(false));
+    assert_eq!(vars.get("same").unwrap(), &Value::Boolean(true));
+    assert_eq!(vars.get("strings").unwrap(), &Value::Boolean(true));
+    assert_eq!(vars.get("mixed").unwrap(), &Value::Boolean(false));
+}
+
+#[test]
+fn chained_assignment_sets_every_target() {
+    let src = r#"
+    var a = 0;
+    var b = 0;
+    var result = a = b = 5;
+    var sum = (a = 2) + b;
+    "#;
+
+    let interp = run_program(src).expect("should run");
+    let vars = interp.get_variables();
+    assert_eq!(vars.get("b").unwrap(), &Value::Number(5.0));
+    assert_eq!(vars.get("result").unwrap(), &Value::Number(5.0));
+    assert_eq!(vars.get("a").unwrap(), &Value::Number(2.0));
+    assert_eq!(vars.get("sum").unwrap(), &Value::Number(7.0));
+
+    match run_program("var a = 0; a = missing = 5;") {
+        Err(msg) => assert!(msg.contains("Undefined variable 'missing'"), "{msg}"),
+        Ok(_) => panic!("assigning an undeclared variable should fail"),
+    }
+}
+
+#[test]
+fn join_path_uses_platform_separator() {
+    let interp = run_program(r#"var p = join_path("a", "b", "c.txt");"#).expect("should run");
+    let expected: std::path::PathBuf = ["a", "b", "c.txt"].iter().collect();
+    assert_eq!(
+        interp.get_variables().get("p").unwrap(),
+        &Value::String(expected.to_string_lossy().into_owned())
+    );
+
+    assert!(run_program("var p = join_path();").is_err());
+    assert!(run_program(r#"var p = join_path("a", 1);"#).is_err());
+}
+
+#[test]
+fn seeded_constants_are_read_only() {
+    use std::collections::HashMap;
+
+    let constants = HashMap::from([("VERSION".to_string(), Value::String("1.0".to_string()))]);
+
+    let run = |src: &str| {
+        let program = Parser::new(Lexer::new(src)).parse().expect("should parse");
+        let mut interp = Interpreter::with_constants(constants.clone());
+        interp.interpret(program).map(|_| interp)
+    };
+
+    let interp = run("var v = VERSION;").expect("should run");
+    assert_eq!(
+        interp.get_variables().get("v").unwrap(),
+        &Value::String("1.0".to_string())
+    );
+
+    for src in ["VERSION = 2;", "var VERSION = 2;"] {
+        match run(src) {
+            Err(RuntimeError::AssignToConst(name)) => assert_eq!(name, "VERSION"),
+            other => panic!("expected AssignToConst for {src:?}, got {:?}", other),
+        }
+    }
+
+    let mut interp = Interpreter::new();
+    interp.define_constant("PI", Value::Number(3.0));
+    let program = Parser::new(Lexer::new("var p = PI;"))
+        .parse()
+        .expect("should parse");
+    interp.interpret(program).expect("should run");
+    assert_eq!(
+        interp.get_variables().get("p").unwrap(),
+        &Value::Number(3.0)
+    );
+}
+
+#[test]
+fn pad_left_and_pad_right() {
+    let src = r#"
+    var zeros = pad_left("7", 3, "0");
+    var spaces = pad_right("ab", 4);
+    var unchanged = pad_left("wide", 2, "*");
+    "#;
+
+    let interp = run_program(src).expect("should run");
+    let vars = interp.get_variables();
+    assert_eq!(
+        vars.get("zeros").unwrap(),
+        &Value::String("007".to_string())
+    );
+    assert_eq!(
+        vars.get("spaces").unwrap(),
+        &Value::String("ab  ".to_string())
+    );
+    assert_eq!(
+        vars.get("unchanged").unwrap(),
+        &Value::String("wide".to_string())
+    );
+
+    assert!(run_program(r#"var bad = pad_left("7", 3, "00");"#).is_err());
+    assert!(run_program(r#"var bad = pad_left("7", 1.5);"#).is_err());
+    assert!(run_program(r#"var bad = pad_left("x", 1e18);"#).is_err());
+
+    for (src, expected_count, found_count) in [
+        (r#"pad_left("x")"#, 2, 1),
+        (r#"pad_right("x", 3, "*", 4)"#, 3, 4),
+    ] {
+        match eval_expr(src) {
+            Err(RuntimeError::ArityMismatch {
+                expected, found, ..
+            }) => assert_eq!((expected, found), (expected_count, found_count)),
+            other => panic!("expected ArityMismatch for {src}, got {:?}", other),
+        }
+    }
+    assert!(run_program(r#"var bad = pad_right(7, 3);"#).is_err());
+}
+
+#[test]
+fn disabling_builtins_keeps_registered_functions() {
+    fn double(args: Vec<Value>) -> Result<Value, RuntimeError> {
+        match args.as_slice() {
+            [Value::Number(n)] => Ok(Value::Number(n * 2.0)),
+            _ => Err(RuntimeError::TypeError(
+                "double expects a number".to_string(),
+            )),
+        }
+    }
+
+    let mut interp = Interpreter::new();
+    interp.register_function("double", double);
+    interp.set_builtins_enabled(false);
+
+    let program = Parser::new(Lexer::new("var d = double(21);"))
+        .parse()
+        .expect("should parse");
+    interp
+        .interpret(program)
+        .expect("registered function should run");
+    assert_eq!(
+        interp.get_variables().get("d").unwrap(),
+        &Value::Number(42.0)
+    );
+
+    let program = Parser::new(Lexer::new(r#"println("hi");"#))
+        .parse()
+        .expect("should parse");
+    match interp.interpret(program) {
+        Err(RuntimeError::UndefinedFunction(name)) => assert_eq!(name, "println"),
+        other => panic!("expected UndefinedFunction, got {:?}", other),
+    }
+}
+
+#[test]
+fn values_can_be_hash_map_keys() {
+    use std::collections::HashMap;
+
+    let mut map = HashMap::new();
+    map.insert(Value::Number(1.0), "one");
+    map.insert(Value::Boolean(true), "yes");
+    map.insert(Value::String("1".to_string()), "string one");
+    map.insert(Value::Null, "nothing");
+
+    assert_eq!(map.get(&Value::Number(1.0)), Some(&"one"));
+    assert_eq!(map.get(&Value::Boolean(true)), Some(&"yes"));
+    assert_eq!(map.get(&Value::Boolean(false)), None);
+    assert_eq!(
+        map.get(&Value::String("1".to_string())),
+        Some(&"string one")
+    );
+    assert_eq!(map.get(&Value::Null), Some(&"nothing"));
+
+    map.insert(Value::Number(0.0), "zero");
+    assert_eq!(map.get(&Value::Number(-0.0)), Some(&"zero"));
+}
+
+#[test]
+fn sleep_respects_max_sleep_limit() {
+    use simple_script_compiler::interpreter::Limits;
+    use std::time::{Duration, Instant};
+
+    let limits = Limits {
+        max_sleep: Some(Duration::from_millis(10)),
+        ..Limits::default()
+    };
+
+    let start = Instant::now();
+    let program = Parser::new(Lexer::new("sleep(0);")).parse().unwrap();
+    let value = Interpreter::with_limits(limits.clone())
+        .evaluate(program)
+        .expect("sleep(0) should succeed");
+    assert_eq!(value, Value::Null);
+    assert!(start.elapsed() < Duration::from_secs(1));
+
+    let program = Parser::new(Lexer::new("sleep(60);")).parse().unwrap();
+    match Interpreter::with_limits(limits.clone()).interpret(program) {
+        Err(RuntimeError::SleepLimitExceeded(limit)) => {
+            assert_eq!(limit, Duration::from_millis(10))
+        }
+        other => panic!("expected SleepLimitExceeded, got {:?}", other),
+    }
+
+    let program = Parser::new(Lexer::new("sleep(-1);")).parse().unwrap();
+    match Interpreter::with_limits(limits).interpret(program) {
+        Err(RuntimeError::TypeError(_)) => {}
+        other => panic!("expected TypeError, got {:?}", other),
+    }
+}
+
+#[test]
+fn timeout_interrupts_slow_scripts() {
+    use std::time::Duration;
+
+    let slow = "sleep(0.01); ".repeat(100);
+    let program = Parser::new(Lexer::new(&slow)).parse().unwrap();
+
+    let mut interp = Interpreter::new();
+    interp.set_timeout(Duration::from_millis(30));
+    match interp.interpret(program) {
+        Err(RuntimeError::Timeout) => {}
+        other => panic!("expected Timeout, got {:?}", other),
+    }
+
+    let mut interp = Interpreter::new();
+    interp.set_timeout(Duration::from_secs(60));
+    interp
+        .interpret(Parser::new(Lexer::new("var x = 1 + 2;")).parse().unwrap())
+        .expect("fast script finishes before the deadline");
+
+    let mut interp = Interpreter::new();
+    interp.set_timeout(Duration::from_millis(30));
+    let started = std::time::Instant::now();
+    match interp.interpret(Parser::new(Lexer::new("sleep(5);")).parse().unwrap()) {
+        Err(RuntimeError::Timeout) => {}
+        other => panic!("expected Timeout, got {:?}", other),
+    }
+    assert!(started.elapsed() < Duration::from_secs(2));
+
+    std::thread::sleep(Duration::from_millis(50));
+    interp
+        .interpret(Parser::new(Lexer::new("var x = 1 + 2;")).parse().unwrap())
+        .expect("each run gets a fresh deadline");
+}
+
+#[test]
+fn shebang_line_is_ignored() {
+    let body = "var x = 2 + 3;\nprintln(x);\n";
+    let with_shebang = format!("#!/usr/bin/env simple-script\n{body}");
+
+    let plain = Interpreter::run_str(body).expect("should run");
+    let shebang = Interpreter::run_str(&with_shebang).expect("should run with shebang");
+    assert_eq!(plain.0, "5\n");
+    assert_eq!(plain, shebang);
+}
+
+#[test]
+fn fork_does_not_leak_writes_to_parent() {
+    let mut parent = Interpreter::new();
+    parent
+        .interpret(Parser::new(Lexer::new("var x = 1;")).parse().unwrap())
+        .unwrap();
+
+    let mut child = parent.fork();
+    let value = child
+        .evaluate(
+            Parser::new(Lexer::new("x = x + 1; var y = x; y"))
+                .parse()
+                .unwrap(),
+        )
+        .expect("fork sees parent variables");
+    assert_eq!(value, Value::Number(2.0));
+
+    let value = parent
+        .evaluate(Parser::new(Lexer::new("x")).parse().unwrap())
+        .unwrap();
+    assert_eq!(value, Value::Number(1.0));
+    assert!(
+        parent
+            .evaluate(Parser::new(Lexer::new("y")).parse().unwrap())
+            .is_err()
+    );
+}
+
+#[test]
+fn fork_shares_parent_output_until_redirected() {
+    let parent_out = SharedBuffer::default();
+    let mut parent = Interpreter::new();
+    parent.set_output(parent_out.clone());
+
+    let mut child = parent.fork();
+    child
+        .interpret(Parser::new(Lexer::new("print(1);")).parse().unwrap())
+        .unwrap();
+    assert_eq!(parent_out.contents(), "1");
+
+    let child_out = SharedBuffer::default();
+    child.set_output(child_out.clone());
+    child
+        .interpret(Parser::new(Lexer::new("print(2);")).parse().unwrap())
+        .unwrap();
+    assert_eq!(child_out.contents(), "2");
+    assert_eq!(parent_out.contents(), "1");
+}
+
+#[test]
+fn assert_type_returns_value_or_fails() {
+    let program = Parser::new(Lexer::new(r#"assert_type(5, "number")"#))
+        .parse()
+        .unwrap();
+    assert_eq!(
+        Interpreter::new().evaluate(program).unwrap(),
+        Value::Number(5.0)
+    );
+
+    let program = Parser::new(Lexer::new(r#"assert_type("x", "number")"#))
+        .parse()
+        .unwrap();
+    match Interpreter::new().evaluate(program) {
+        Err(RuntimeError::AssertionFailed(msg)) => {
+            assert!(
+                msg.contains("number") && msg.contains("string"),
+                "message: {msg}"
+            );
+        }
+        other => panic!("expected AssertionFailed, got {:?}", other),
+    }
+}
+
+#[test]
+fn value_digest_is_structural() {
+    let a = Value::String("hello".to_string());
+    let b = Value::String("hel".to_string() + "lo");
+    assert_eq!(a.digest(), b.digest());
+    assert_ne!(a.digest(), Value::String("world".to_string()).digest());
+
+    assert_eq!(Value::Number(0.0).digest(), Value::Number(-0.0).digest());
+    assert_eq!(
+        Value::Number(f64::NAN).digest(),
+        Value::Number(-f64::NAN).digest()
+    );
+    assert_eq!(
+        Value::Bytes(vec![1, 2, 3]).digest(),
+        Value::Bytes(vec![1, 2, 3]).digest()
+    );
+    assert_ne!(Value::Number(1.0).digest(), Value::Boolean(true).digest());
+
+    // Pinned so an accidental change to the encoding is caught.
+    assert_eq!(Value::Null.digest(), 0xaf63_b94c_8601_b113);
+    assert_eq!(Value::Boolean(true).digest(), 0x0839_5307_b4f1_348c);
+    assert_eq!(Value::Number(1.5).digest(), 0x5095_a3dc_8e3e_5f39);
+    assert_eq!(
+        Value::String("hi".to_string()).digest(),
+        0x95da_31d4_fbcc_a672
+    );
+}
+
+#[test]
+fn max_call_args_rejects_wide_calls() {
+    use simple_script_compiler::interpreter::Limits;
+
+    let limits = Limits {
+        max_call_args: Some(2),
+        ..Limits::default()
+    };
+
+    let program = Parser::new(Lexer::new("typeof(1);")).parse().unwrap();
+    Interpreter::with_limits(limits.clone())
+        .interpret(program)
+        .expect("within the cap");
+
+    let program = Parser::new(Lexer::new("print(1, 2, 3);")).parse().unwrap();
+    match Interpreter::with_limits(limits).interpret(program) {
+        Err(RuntimeError::ArgumentLimitExceeded(limit)) => assert_eq!(limit, 2),
+        other => panic!("expected ArgumentLimitExceeded, got {:?}", other),
+    }
+}
+
+#[test]
+fn round_to_supports_half_up_and_half_even() {
+    assert_eq!(
+        eval_expr(r#"round_to(2.5, 0, "half_even")"#).unwrap(),
+        Value::Number(2.0)
+    );
+    assert_eq!(
+        eval_expr(r#"round_to(2.5, 0, "half_up")"#).unwrap(),
+        Value::Number(3.0)
+    );
+    assert_eq!(
+        eval_expr(r#"round_to(3.5, 0, "half_even")"#).unwrap(),
+        Value::Number(4.0)
+    );
+    assert_eq!(
+        eval_expr(r#"round_to(1.25, 1, "half_even")"#).unwrap(),
+        Value::Number(1.2)
+    );
+    assert_eq!(
+        eval_expr(r#"round_to(1.25, 1, "half_up")"#).unwrap(),
+        Value::Number(1.3)
+    );
+    assert_eq!(
+        eval_expr(r#"round_to(-2.5, 0, "half_up")"#).unwrap(),
+        Value::Number(-2.0)
+    );
+    assert_eq!(
+        eval_expr(r#"round_to(-1.25, 1, "half_up")"#).unwrap(),
+        Value::Number(-1.2)
+    );
+    assert_eq!(
+        eval_expr(r#"round_to(-2.5, 0, "half_even")"#).unwrap(),
+        Value::Number(-2.0)
+    );
+    assert_eq!(
+        eval_expr(r#"round_to(0.49999999999999994, 0, "half_up")"#).unwrap(),
+        Value::Number(0.0)
+    );
+    assert_eq!(
+        eval_expr(r#"round_to(1.25, 400, "half_up")"#).unwrap(),
+        Value::Number(1.25)
+    );
+    assert_eq!(
+        eval_expr(r#"round_to(1e300, 10, "half_even")"#).unwrap(),
+        Value::Number(1e300)
+    );
+
+    match eval_expr(r#"round_to(2.5, 0, "down")"#) {
+        Err(RuntimeError::TypeError(_)) => {}
+        other => panic!("expected TypeError, got {:?}", other),
+    }
+}
+
+#[test]
+fn value_accessors_extract_or_report_type_errors() {
+    assert_eq!(Value::Number(1.5).as_number().unwrap(), 1.5);
+    assert_eq!(Value::String("hi".to_string()).as_string().unwrap(), "hi");
+    assert!(Value::Boolean(true).as_bool().unwrap());
+    assert_eq!(Value::Bytes(vec![7]).as_bytes().unwrap(), &[7]);
+
+    match Value::String("1".to_string()).as_number() {
+        Err(RuntimeError::TypeError(msg)) => assert_eq!(msg, "expected number, got string"),
+        other => panic!("expected TypeError, got {:?}", other),
+    }
+    match Value::Number(1.0).as_string() {
+        Err(RuntimeError::TypeError(msg)) => assert_eq!(msg, "expected string, got number"),
+        other => panic!("expected TypeError, got {:?}", other),
+    }
+    match Value::Null.as_bool() {
+        Err(RuntimeError::TypeError(msg)) => assert_eq!(msg, "expected boolean, got null"),
+        other => panic!("expected TypeError, got {:?}", other),
+    }
+    match Value::Boolean(false).as_bytes() {
+        Err(RuntimeError::TypeError(msg)) => assert_eq!(msg, "expected bytes, got boolean"),
+        other => panic!("expected TypeError, got {:?}", other),
+    }
+}
+
+#[test]
+fn eval_runs_source_in_current_scope_when_enabled() {
+    use simple_script_compiler::interpreter::Limits;
+
+    let program = Parser::new(Lexer::new(r#"eval("var x = 2 + 3; x")"#))
+        .parse()
+        .unwrap();
+    match Interpreter::new().evaluate(program.clone()) {
+        Err(RuntimeError::PermissionDenied(_)) => {}
+        other => panic!("expected PermissionDenied, got {:?}", other),
+    }
+
+    let mut interp = Interpreter::new();
+    interp.set_eval_enabled(true);
+    assert_eq!(interp.evaluate(program).unwrap(), Value::Number(5.0));
+    let x = Parser::new(Lexer::new("x")).parse().unwrap();
+    assert_eq!(interp.evaluate(x).unwrap(), Value::Number(5.0));
+
+    let bad = Parser::new(Lexer::new(r#"eval("var = ;")"#))
+        .parse()
+        .unwrap();
+    assert!(matches!(interp.evaluate(bad), Err(RuntimeError::Parse(_))));
+
+    let mut interp = Interpreter::with_limits(Limits {
+        max_steps: Some(5),
+        ..Limits::default()
+    });
+    interp.set_eval_enabled(true);
+    let heavy = Parser::new(Lexer::new(r#"eval("1 + 2 + 3 + 4 + 5")"#))
+        .parse()
+        .unwrap();
+    assert!(matches!(
+        interp.evaluate(heavy),
+        Err(RuntimeError::StepLimitExceeded(5))
+    ));
+}
+
+#[test]
+fn string_affix_and_trim_helpers() {
+    let (output, _) = Interpreter::run_str(
+        r#"println(ends_with("file.txt", ".txt"), starts_with("file.txt", "txt"));
+        print("[", trim_start("  a  "), "]", "[", trim_end("  a  "), "]");"#,
+    )
+    .expect("should run");
+    assert_eq!(output, "true false\n[ a   ] [   a ]");
+
+    assert!(Interpreter::run_str("ends_with(1, \"x\");").is_err());
+    assert!(Interpreter::run_str("trim_start(true);").is_err());
+    assert!(Interpreter::run_str("trim_end();").is_err());
+}
+
+#[test]
+fn with_variables_seeds_mutable_bindings() {
+    use std::collections::HashMap;
+
+    let variables = HashMap::from([
+        ("name".to_string(), Value::String("Ada".to_string())),
+        ("count".to_string(), Value::Number(2.0)),
+    ]);
+
+    let mut interp = Interpreter::with_variables(variables);
+    let program = Parser::new(Lexer::new(
+        r#"count = count + 1; var greeting = "Hi " + name;"#,
+    ))
+    .parse()
+    .expect("should parse");
+    interp.interpret(program).expect("should run");
+
+    assert_eq!(
+        interp.get_variables().get("greeting").unwrap(),
+        &Value::String("Hi Ada".to_string())
+    );
+    assert_eq!(
+        interp.get_variables().get("count").unwrap(),
+        &Value::Number(3.0)
+    );
+}
+
+#[test]
+fn sci_formats_in_scientific_notation() {
+    let (output, _) = Interpreter::run_str(
+        "println(sci(12345, 2)); println(sci(0.000123456, 3)); print(sci(5, 1));",
+    )
+    .expect("should run");
+    assert_eq!(output, "1.2e4\n1.23e-4\n5e0");
+
+    assert!(Interpreter::run_str("sci(1, 0);").is_err());
+    assert!(Interpreter::run_str("sci(1, 1.5);").is_err());
+    assert!(Interpreter::run_str(r#"sci("1", 2);"#).is_err());
+}
+
+#[test]
+fn values_order_within_a_type() {
+    use std::cmp::Ordering;
+
+    let mut numbers = vec![Value::Number(3.0), Value::Number(-1.0), Value::Number(2.5)];
+    numbers.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    assert_eq!(
+        numbers,
+        vec![Value::Number(-1.0), Value::Number(2.5), Value::Number(3.0)]
+    );
+
+    let apple = Value::String("apple".to_string());
+    let banana = Value::String("banana".to_string());
+    assert!(apple < banana);
+    assert_eq!(apple.partial_cmp(&apple), Some(Ordering::Equal));
+
+    assert_eq!(Value::Number(1.0).partial_cmp(&apple), None);
+    assert_eq!(Value::Null.partial_cmp(&Value::Boolean(false)), None);
+    assert_eq!(
+        Value::Number(f64::NAN).partial_cmp(&Value::Number(1.0)),
+        None
+    );
+}
+
+#[test]
+fn error_on_infinity_rejects_float_overflow() {
+    let src = "var big = 100000000000000000000; big * big * big * big * big * big * big * big * big * big * big * big * big * big * big * big";
+
+    let program = Parser::new(Lexer::new(src)).parse().unwrap();
+    assert_eq!(
+        Interpreter::new().evaluate(program.clone()).unwrap(),
+        Value::Number(f64::INFINITY)
+    );
+
+    let mut interp = Interpreter::new();
+    interp.set_error_on_infinity(true);
+    assert!(matches!(
+        interp.evaluate(program),
+        Err(RuntimeError::Overflow)
+    ));
+
+    let program = Parser::new(Lexer::new("Infinity + 1")).parse().unwrap();
+    assert_eq!(
+        interp.evaluate(program).unwrap(),
+        Value::Number(f64::INFINITY)
+    );
+}
+
+#[test]
+fn value_kind_and_type_name_match_typeof() {
+    use simple_script_compiler::interpreter::ValueKind;
+
+    assert_eq!(Value::Number(1.0).kind(), ValueKind::Number);
+    assert_eq!(Value::Number(1.0).type_name(), "number");
+    assert_eq!(Value::Null.kind(), ValueKind::Null);
+    assert_eq!(Value::Bytes(vec![]).type_name(), "bytes");
+
+    let program = Parser::new(Lexer::new(r#"typeof("s")"#)).parse().unwrap();
+    assert_eq!(
+        Interpreter::new().evaluate(program).unwrap(),
+        Value::String(Value::String(String::new()).type_name().to_string())
+    );
+}
+
+#[test]
+fn coalesce_returns_first_non_null() {
+    assert_eq!(
+        eval_expr(r#"coalesce(try_num("a"), try_num("b"), 3, 4)"#).unwrap(),
+        Value::Number(3.0)
+    );
+    assert_eq!(
+        eval_expr(r#"coalesce(try_num("a"), try_num("b"))"#).unwrap(),
+        Value::Null
+    );
+    assert_eq!(eval_expr("coalesce()").unwrap(), Value::Null);
+
+    let (output, value) = Interpreter::run_str(r#"coalesce(1, print("evaluated"))"#).unwrap();
+    assert_eq!(value, Value::Number(1.0));
+    assert_eq!(output, "evaluated");
+}
+
+#[test]
+fn debug_assert_skips_evaluation_when_disabled() {
+    let parse = |src: &str| Parser::new(Lexer::new(src)).parse().unwrap();
+
+    let mut interp = Interpreter::new();
+    interp
+        .interpret(parse("debug_assert(1 == 1);"))
+        .expect("holds");
+    match interp.interpret(parse("debug_assert(1 == 2);")) {
+        Err(RuntimeError::AssertionFailed(_)) => {}
+        other => panic!("expected AssertionFailed, got {:?}", other),
+    }
+
+    let mut interp = Interpreter::new();
+    interp.set_debug_assertions(false);
+    let value = interp
+        .evaluate(parse("var hits = 0; debug_assert((hits = 1) == 2); hits"))
+        .expect("disabled debug_assert is a no-op");
+    assert_eq!(value, Value::Number(0.0));
+}
+
+#[test]
+fn num_parses_like_number_literals() {
+    assert_eq!(
+        eval_expr(r#"num("1_000")"#).unwrap(),
+        eval_expr("1_000").unwrap()
+    );
+    assert_eq!(eval_expr(r#"num("1.5e3")"#).unwrap(), Value::Number(1500.0));
+    assert_eq!(eval_expr(r#"num(" -2.5 ")"#).unwrap(), Value::Number(-2.5));
+
+    for bad in [
+        r#"num("1__0")"#,
+        r#"num("_1")"#,
+        r#"num("abc")"#,
+        r#"num("inf")"#,
+    ] {
+        match eval_expr(bad) {
+            Err(RuntimeError::TypeError(_)) => {}
+            other => panic!("expected TypeError for {bad}, got {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn dump_and_restore_state_round_trips_bindings() {
+    let mut original = Interpreter::new();
+    original.define_constant("LIMIT", Value::Number(10.0));
+    original
+        .interpret(
+            Parser::new(Lexer::new(r#"var a = 1; var s = "x";"#))
+                .parse()
+                .unwrap(),
+        )
+        .unwrap();
+    let snapshot = original.dump_state();
+
+    let mut restored = Interpreter::new();
+    restored.restore_state(snapshot.clone());
+    assert_eq!(restored.get_variables(), original.get_variables());
+    assert_eq!(restored.dump_state(), snapshot);
+
+    let value = restored
+        .evaluate(Parser::new(Lexer::new("a + LIMIT")).parse().unwrap())
+        .unwrap();
+    assert_eq!(value, Value::Number(11.0));
+    assert!(matches!(
+        restored.interpret(Parser::new(Lexer::new("LIMIT = 1;")).parse().unwrap()),
+        Err(RuntimeError::AssignToConst(_))
+    ));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn state_snapshot_serializes_with_serde() {
+    use simple_script_compiler::interpreter::StateSnapshot;
+
+    let mut original = Interpreter::with_variables(
+        [
+            ("raw".to_string(), Value::Bytes(vec![0, 255])),
+            ("n".to_string(), Value::Null),
+        ]
+        .into_iter()
+        .collect(),
+    );
+    original.define_constant("LIMIT", Value::Number(10.0));
+    original
+        .interpret(
+            Parser::new(Lexer::new(r#"var s = "x"; var b = true; var f = -2.5;"#))
+                .parse()
+                .unwrap(),
+        )
+        .unwrap();
+    let snapshot = original.dump_state();
+
+    let json = serde_json::to_string(&snapshot).expect("should serialize");
+    let decoded: StateSnapshot = serde_json::from_str(&json).expect("should deserialize");
+    assert_eq!(decoded, snapshot);
+
+    let mut restored = Interpreter::new();
+    restored.restore_state(decoded);
+    assert_eq!(restored.get_variables(), original.get_variables());
+}
+
+#[test]
+fn char_at_indexes_unicode_scalars() {
+    assert_eq!(
+        eval_expr(r#"char_at("hello", 1)"#).unwrap(),
+        Value::String("e".to_string())
+    );
+    assert_eq!(
+        eval_expr(r#"char_at("héllo😀", 5)"#).unwrap(),
+        Value::String("😀".to_string())
+    );
+
+    match eval_expr(r#"char_at("héllo", 5)"#) {
+        Err(RuntimeError::TypeError(msg)) => assert!(msg.contains("out of range"), "{msg}"),
+        other => panic!("expected TypeError, got {:?}", other),
+    }
+    assert!(eval_expr(r#"char_at("abc", -1)"#).is_err());
+}
+
+#[test]
+fn number_format_groups_thousands_in_output() {
+    use simple_script_compiler::interpreter::NumberFormat;
+
+    let program = Parser::new(Lexer::new(
+        r#"println(1234567.5, -1000, 999, "1000"); print(format_number(1234567.5));"#,
+    ))
+    .parse()
+    .unwrap();
+
+    let run = |format: NumberFormat| {
+        let capture = SharedBuffer::default();
+        let mut interp = Interpreter::new();
+        interp.set_output(capture.clone());
+        interp.set_number_format(format);
+        interp.interpret(program.clone()).unwrap();
+        capture.contents()
+    };
+
+    assert_eq!(
+        run(NumberFormat::default()),
+        "1234567.5 -1000 999 1000\n1234567.5"
+    );
+    assert_eq!(
+        run(NumberFormat {
+            decimal_separator: '.',
+            grouping_separator: Some(','),
+        }),
+        "1,234,567.5 -1,000 999 1000\n1,234,567.5"
+    );
+    assert_eq!(
+        run(NumberFormat {
+            decimal_separator: ',',
+            grouping_separator: Some('.'),
+        }),
+        "1.234.567,5 -1.000 999 1000\n1.234.567,5"
+    );
+}
+
+#[test]
+fn define_module_registers_namespaced_functions() {
+    use simple_script_compiler::interpreter::NativeFn;
+    use std::collections::HashMap;
+
+    fn square(args: Vec<Value>) -> Result<Value, RuntimeError> {
+        let n = args[0].as_number()?;
+        Ok(Value::Number(n * n))
+    }
+
+    let mut interp = Interpreter::new();
+    interp.define_module(
+        "math",
+        HashMap::from([("square".to_string(), square as NativeFn)]),
+    );
+
+    let program = Parser::new(Lexer::new("math.square(5)")).parse().unwrap();
+    assert_eq!(interp.evaluate(program).unwrap(), Value::Number(25.0));
+
+    let program = Parser::new(Lexer::new("math.cube(5)")).parse().unwrap();
+    assert!(matches!(
+        interp.evaluate(program),
+        Err(RuntimeError::UndefinedFunction(name)) if name == "math.cube"
+    ));
+
+    assert!(Parser::new(Lexer::new("math.square;")).parse().is_err());
+    assert!(Parser::new(Lexer::new("math.(1);")).parse().is_err());
+}
+
+#[test]
+fn as_index_accepts_only_non_negative_integers() {
+    assert_eq!(Value::Number(3.0).as_index().unwrap(), 3);
+    assert_eq!(Value::Number(0.0).as_index().unwrap(), 0);
+
+    for (value, message) in [
+        (
+            Value::Number(1.5),
+            "expected a non-negative integer, got 1.5",
+        ),
+        (
+            Value::Number(-2.0),
+            "expected a non-negative integer, got -2",
+        ),
+        (
+            Value::String("1".to_string()),
+            "expected a non-negative integer, got string",
+        ),
+        (
+            Value::Number(1e18),
+            "expected an integer no larger than 16777216, got 1000000000000000000",
+        ),
+        (
+            Value::Number(f64::INFINITY),
+            "expected an integer no larger than 16777216, got inf",
+        ),
+    ] {
+        match value.as_index() {
+            Err(RuntimeError::TypeError(msg)) => assert_eq!(msg, message),
+            other => panic!("expected TypeError, got {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn modulo_operator() {
+    assert_eq!(eval_expr("10 % 3 == 1").unwrap(), Value::Boolean(true));
+    assert_eq!(eval_expr("-7 % 3").unwrap(), Value::Number(-1.0));
+    assert_eq!(eval_expr("7.5 % 2").unwrap(), Value::Number(1.5));
+    assert_eq!(eval_expr("1 + 10 % 4 * 2").unwrap(), Value::Number(5.0));
+
+    match eval_expr("5 % 0") {
+        Err(RuntimeError::TypeError(msg)) => assert_eq!(msg, "Modulo by zero"),
+        other => panic!("expected TypeError, got {:?}", other),
+    }
+}
+
+#[test]
+fn if_else_selects_branch_by_boolean_condition() {
+    let (output, _) = Interpreter::run_str(
+        r#"
+        var x = 5;
+        if (x == 5) { println("then"); var hit = true; }
+        if (x == 6) { println("wrong"); } else { println("else"); }
+        if (x == 6) { println("skipped"); }
+        "#,
+    )
+    .expect("should run");
+    assert_eq!(output, "then\nelse\n");
+
+    let program = Parser::new(Lexer::new(r#"if (1) { println("no"); }"#))
+        .parse()
+        .unwrap();
+    match Interpreter::new().interpret(program) {
+        Err(RuntimeError::TypeError(msg)) => assert!(msg.contains("boolean"), "{msg}"),
+        other => panic!("expected TypeError, got {:?}", other),
+    }
+}
+
+#[test]
+fn while_loops_run_until_condition_is_false() {
+    use simple_script_compiler::interpreter::Limits;
+
+    let (output, value) = Interpreter::run_str(
+        r#"
+        var i = 0;
+        var sum = 0;
+        while (i != 5) { i = i + 1; sum = sum + i; }
+        while (false) { println("never"); }
+        sum
+        "#,
+    )
+    .expect("should run");
+    assert_eq!(output, "");
+    assert_eq!(value, Value::Number(15.0));
+
+    let forever = Parser::new(Lexer::new("var n = 0; while (true) { n = n + 1; }"))
+        .parse()
+        .unwrap();
+    let mut interp = Interpreter::with_limits(Limits {
+        max_loop_iterations: Some(100),
+        ..Limits::default()
+    });
+    match interp.interpret(forever) {
+        Err(RuntimeError::IterationLimitExceeded(limit)) => assert_eq!(limit, 100),
+        other => panic!("expected IterationLimitExceeded, got {:?}", other),
+    }
+    assert_eq!(
+        interp.get_variables().get("n").unwrap(),
+        &Value::Number(100.0)
+    );
+
+    assert!(Interpreter::run_str("while (1) { }").is_err());
+}
+
+#[test]
+fn buffered_output_is_written_on_flush_or_program_end() {
+    thread_local! {
+        static OUTPUT: std::cell::RefCell<SharedBuffer> = Default::default();
+    }
+
+    fn written(_: Vec<Value>) -> Result<Value, RuntimeError> {
+        Ok(Value::String(OUTPUT.with_borrow(SharedBuffer::contents)))
+    }
+
+    let program = Parser::new(Lexer::new(
+        r#"print("a"); print("b"); var before = written(); flush(); var after = written(); print("c"); println("d");"#,
+    ))
+    .parse()
+    .unwrap();
+
+    let run = |buffered: bool| {
+        let output = SharedBuffer::default();
+        OUTPUT.replace(output.clone());
+        let mut interp = Interpreter::new();
+        interp.set_output(output.clone());
+        interp.register_function("written", written);
+        interp.set_output_buffered(buffered);
+        interp.interpret(program.clone()).unwrap();
+        let vars = interp.get_variables();
+        (
+            vars.get("before").unwrap().to_string(),
+            vars.get("after").unwrap().to_string(),
+            output.contents(),
+        )
+    };
+
+    assert_eq!(run(false), ("ab".into(), "ab".into(), "abcd\n".into()));
+    assert_eq!(run(true), ("".into(), "ab".into(), "abcd\n".into()));
+}
+
+#[test]
+fn reassignment_requires_prior_declaration() {
+    let mut interp = Interpreter::new();
+    let value = interp
+        .evaluate(
+            Parser::new(Lexer::new("var x = 1; x = x + 41; x"))
+                .parse()
+                .unwrap(),
+        )
+        .expect("reassigning a declared variable works");
+    assert_eq!(value, Value::Number(42.0));
+
+    let program = Parser::new(Lexer::new("y = 1;")).parse().unwrap();
+    match Interpreter::new().interpret(program) {
+        Err(RuntimeError::UndefinedVariable(name)) => assert_eq!(name, "y"),
+        other => panic!("expected UndefinedVariable, got {:?}", other),
+    }
+
+    let program = Parser::new(Lexer::new("x + 1;")).parse().unwrap();
+    assert!(matches!(
+        program.statements[0],
+        simple_script_compiler::parser::Statement::Expression(
+            simple_script_compiler::parser::Expression::Binary { .. }
+        )
+    ));
+}
@@ -1,5 +1,18 @@
 use simple_script_compiler::lexer::{Lexer, Literal, Token};
 
+fn tokens(src: &str) -> Vec<Token> {
+    lexer_tokens(Lexer::new(src))
+}
+
+fn lexer_tokens(mut lexer: Lexer) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    while *lexer.current_token() != Token::EOF {
+        tokens.push(lexer.current_token().clone());
+        lexer.advance();
+    }
+    tokens
+}
+
 #[test]
 fn lexer_basic_tokens_and_literals() {
     let src = r#"var x = 123; var s = "hello"; var f = 3.14; var b = true;"#;
@@ -49,3 +62,220 @@ fn lexer_number_edge_cases_and_negative_literals() {
     assert_eq!(numbers[2], 10.0);
     assert_eq!(numbers[3], -123.0);
 }
+
+#[test]
+fn lexer_equality_operators() {
+    let tokens = tokens("a == b != c = d");
+
+    assert_eq!(
+        tokens,
+        vec![
+            Token::Identifier("a".to_string()),
+            Token::EqualEqual,
+            Token::Identifier("b".to_string()),
+            Token::NotEqual,
+            Token::Identifier("c".to_string()),
+            Token::Equals,
+            Token::Identifier("d".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn lexer_infinity_and_nan_keywords() {
+    let mut lexer = Lexer::new("Infinity -Infinity NaN Infinitely");
+
+    let mut numbers = Vec::new();
+    let mut identifiers = Vec::new();
+    loop {
+        match lexer.current_token() {
+            Token::Literal(Literal::Number(n)) => numbers.push(*n),
+            Token::Identifier(name) => identifiers.push(name.clone()),
+            Token::EOF => break,
+            other => panic!("unexpected token {:?}", other),
+        }
+        lexer.advance();
+    }
+
+    assert_eq!(numbers.len(), 3);
+    assert_eq!(numbers[0], f64::INFINITY);
+    assert_eq!(numbers[1], f64::NEG_INFINITY);
+    assert!(numbers[2].is_nan());
+    assert_eq!(identifiers, vec!["Infinitely".to_string()]);
+}
+
+#[test]
+fn lexer_bitwise_operators() {
+    let tokens = tokens("& | ^^ << >>");
+
+    assert_eq!(
+        tokens,
+        vec![
+            Token::BitAnd,
+            Token::BitOr,
+            Token::BitXor,
+            Token::ShiftLeft,
+            Token::ShiftRight,
+        ]
+    );
+
+    for op in ['^', '<', '>'] {
+        let source = format!("1 {op} 2");
+        let mut lexer = Lexer::new(&source);
+        lexer.advance();
+        assert_eq!(
+            *lexer.current_token(),
+            Token::Error(format!("unexpected '{op}'")),
+            "source: {source}"
+        );
+    }
+}
+
+#[test]
+fn lexer_from_reader_matches_str_lexer() {
+    let src =
+        "var x = -Infinity;\nvar s = \"héllo\" \\\n+ \"!\";\nswitch (x) { case .5: print(s); }\n";
+
+    let from_str = lexer_tokens(Lexer::new(src));
+    let from_reader = lexer_tokens(Lexer::from_reader(std::io::Cursor::new(src)));
+
+    assert!(!from_str.is_empty());
+    assert_eq!(from_str, from_reader);
+}
+
+#[test]
+fn lexer_from_reader_reports_read_errors() {
+    struct Failing(bool);
+
+    impl std::io::Read for Failing {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if std::mem::replace(&mut self.0, true) {
+                return Err(std::io::Error::other("disk on fire"));
+            }
+            let data = b"var a = 1;\n";
+            buf[..data.len()].copy_from_slice(data);
+            Ok(data.len())
+        }
+    }
+
+    let tokens = lexer_tokens(Lexer::from_reader(std::io::BufReader::new(Failing(false))));
+    assert_eq!(tokens.len(), 6);
+    assert!(matches!(&tokens[5], Token::Error(msg) if msg.contains("disk on fire")));
+
+    let src: &[u8] = b"var a = 1;\nvar b = 2;\nvar \xff = 3;\nvar c = 4;\n";
+    let tokens = lexer_tokens(Lexer::from_reader(src));
+    assert!(matches!(tokens.last(), Some(Token::Error(_))));
+    assert!(!tokens.contains(&Token::Identifier("c".to_string())));
+}
+
+#[test]
+fn lexer_skips_hash_comments() {
+    assert_eq!(tokens("var a = 1; # note"), tokens("var a = 1;"));
+    assert_eq!(
+        tokens("#!/usr/bin/env simple-script\n# header\nvar a = 1; # note\nvar b = 2;"),
+        tokens("var a = 1; var b = 2;")
+    );
+}
+
+#[test]
+fn lexer_invalid_unicode_escape_stops_at_closing_quote() {
+    for (content, expected) in [
+        (r"\u{", r"\u{"),
+        (r"\u{zz}", r"\u{zz}"),
+        (r"\u{110000}", r"\u{110000}"),
+    ] {
+        for quote in ['"', '\''] {
+            let source = format!("{quote}{content}{quote} + 1");
+            let mut lexer = Lexer::new(&source);
+            assert_eq!(
+                *lexer.current_token(),
+                Token::Literal(Literal::String(expected.to_string())),
+                "source: {source}"
+            );
+            lexer.advance();
+            assert_eq!(*lexer.current_token(), Token::Add, "source: {source}");
+        }
+    }
+}
+
+#[test]
+fn lexer_escapes_match_across_quote_styles() {
+    let content = r#"a\nb\t\\ \x41 \u{1F600} \u{e9}\0"#;
+    let expected = "a\nb\t\\ A \u{1F600} \u{e9}\0";
+
+    for source in [format!("\"{content}\""), format!("'{content}'")] {
+        let lexer = Lexer::new(&source);
+        assert_eq!(
+            *lexer.current_token(),
+            Token::Literal(Literal::String(expected.to_string())),
+            "source: {source}"
+        );
+    }
+
+    let lexer = Lexer::new(r#""it's \"quoted\"""#);
+    assert_eq!(
+        *lexer.current_token(),
+        Token::Literal(Literal::String("it's \"quoted\"".to_string()))
+    );
+    let lexer = Lexer::new(r#"'say "hi" \'now\''"#);
+    assert_eq!(
+        *lexer.current_token(),
+        Token::Literal(Literal::String("say \"hi\" 'now'".to_string()))
+    );
+}
+
+#[test]
+fn lexer_numbers_accept_separators_and_exponents() {
+    let number = |src: &str| match Lexer::new(src).current_token() {
+        Token::Literal(Literal::Number(n)) => *n,
+        other => panic!("expected number for {src:?}, got {:?}", other),
+    };
+
+    assert_eq!(number("1_000"), 1000.0);
+    assert_eq!(number("1.5e3"), 1500.0);
+    assert_eq!(number("2E-2"), 0.02);
+    assert_eq!(number("-1_0e+1"), -100.0);
+
+    let mut lexer = Lexer::new("2e x");
+    assert_eq!(*lexer.current_token(), Token::Literal(Literal::Number(2.0)));
+    lexer.advance();
+    assert_eq!(*lexer.current_token(), Token::Identifier("e".to_string()));
+}
+
+#[test]
+fn lexer_skips_slash_line_comments() {
+    let plain = tokens("var x = 1; var y = x / 2;");
+    assert!(plain.contains(&Token::Divide));
+
+    assert_eq!(tokens("var x = 1; // set x\nvar y = x / 2;"), plain);
+    assert_eq!(
+        tokens("// header\nvar x = 1;\n   // own line\nvar y = x / 2; // trailing at EOF"),
+        plain
+    );
+    assert_eq!(tokens("var x = 1; var y = x / 2;//"), plain);
+}
+
+#[test]
+fn lexer_skips_block_comments() {
+    assert_eq!(
+        tokens("var x = 1 /* inline */ + 2;"),
+        tokens("var x = 1 + 2;")
+    );
+    assert_eq!(
+        tokens("/* header\n   spanning * lines /\n*/\nvar x = 1;/**/"),
+        tokens("var x = 1;")
+    );
+    assert_eq!(tokens("/* /* */ */"), vec![Token::Multiply, Token::Divide]);
+
+    assert_eq!(
+        tokens("var x = 1; /* never closed\nvar y = 2;"),
+        vec![
+            Token::Var,
+            Token::Identifier("x".to_string()),
+            Token::Equals,
+            Token::Literal(Literal::Number(1.0)),
+            Token::SemiColon,
+            Token::Error("unterminated block comment".to_string()),
+        ]
+    );
+}
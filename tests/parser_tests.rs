@@ -36,3 +36,340 @@ fn parser_parentheses_and_nested_precedence() {
     let program = parser.parse().expect("should parse nested");
     assert!(!program.statements.is_empty());
 }
+
+#[test]
+fn parser_backslash_line_continuation() {
+    let continued = "var total = 1 +\\\n    2 * 3;";
+    let program = Parser::new(Lexer::new(continued))
+        .parse()
+        .expect("should parse continued statement");
+    let expected = Parser::new(Lexer::new("var total = 1 + 2 * 3;"))
+        .parse()
+        .expect("should parse");
+
+    assert_eq!(program.statements.len(), 1);
+    assert_eq!(program, expected);
+}
+
+#[test]
+fn parser_tuple_assignment_count_mismatch() {
+    use simple_script_compiler::parser::ParseError;
+
+    let program = Parser::new(Lexer::new("a, b = 1, 2;"))
+        .parse()
+        .expect("should parse");
+    assert!(matches!(
+        &program.statements[0],
+        Statement::TupleAssignment { names, values } if names.len() == 2 && values.len() == 2
+    ));
+
+    match Parser::new(Lexer::new("a, b = 1;")).parse() {
+        Err(ParseError::AssignmentCountMismatch { targets, values }) => {
+            assert_eq!(targets, 2);
+            assert_eq!(values, 1);
+        }
+        other => panic!("expected AssignmentCountMismatch, got {:?}", other),
+    }
+}
+
+#[test]
+fn program_len_iter_and_count_by() {
+    let src = r#"var a = 1; var b = 2; println(a + b);"#;
+    let program = Parser::new(Lexer::new(src)).parse().expect("should parse");
+
+    assert_eq!(program.len(), 3);
+    assert!(!program.is_empty());
+    assert_eq!(program.iter().count(), 3);
+    assert_eq!(
+        program.count_by(|stmt| matches!(stmt, Statement::VarDeclaration { .. })),
+        2
+    );
+
+    let empty = Parser::new(Lexer::new("")).parse().expect("should parse");
+    assert!(empty.is_empty());
+}
+
+#[test]
+fn parser_switch_rejects_second_default() {
+    let src = r#"switch (1) { default: 1; default: 2; }"#;
+    assert!(Parser::new(Lexer::new(src)).parse().is_err());
+}
+
+#[test]
+fn parser_rejects_oversized_string_literal() {
+    use simple_script_compiler::parser::ParseError;
+
+    let src = r#"var s = "abcdef";"#;
+    assert!(Parser::new(Lexer::new(src)).parse().is_ok());
+
+    let mut parser = Parser::new(Lexer::new(src));
+    parser.set_max_literal_len(5);
+    match parser.parse() {
+        Err(ParseError::LiteralTooLarge { limit, found }) => {
+            assert_eq!(limit, 5);
+            assert_eq!(found, 6);
+        }
+        other => panic!("expected LiteralTooLarge, got {:?}", other),
+    }
+}
+
+fn parse_value(src: &str) -> Expression {
+    let program = Parser::new(Lexer::new(src)).parse().expect("should parse");
+    match program.statements.into_iter().next() {
+        Some(Statement::Expression(expr)) => expr,
+        other => panic!("expected expression statement, got {:?}", other),
+    }
+}
+
+fn num(n: f64) -> Expression {
+    Expression::Literal(simple_script_compiler::lexer::Literal::Number(n))
+}
+
+fn bin(left: Expression, op: BinaryOp, right: Expression) -> Expression {
+    Expression::Binary {
+        left: Box::new(left),
+        op,
+        right: Box::new(right),
+    }
+}
+
+#[test]
+fn parser_precedence_table_builds_expected_trees() {
+    use simple_script_compiler::parser::precedence_of;
+
+    assert!(precedence_of(&BinaryOp::Multiply) > precedence_of(&BinaryOp::Add));
+    assert!(precedence_of(&BinaryOp::Add) > precedence_of(&BinaryOp::ShiftLeft));
+    assert!(precedence_of(&BinaryOp::BitAnd) > precedence_of(&BinaryOp::BitOr));
+    assert!(precedence_of(&BinaryOp::BitOr) > precedence_of(&BinaryOp::Equal));
+    assert_eq!(
+        precedence_of(&BinaryOp::Divide),
+        precedence_of(&BinaryOp::Multiply)
+    );
+
+    assert_eq!(
+        parse_value("1 + 2 * 3;"),
+        bin(
+            num(1.0),
+            BinaryOp::Add,
+            bin(num(2.0), BinaryOp::Multiply, num(3.0))
+        )
+    );
+    assert_eq!(
+        parse_value("1 - 2 - 3;"),
+        bin(
+            bin(num(1.0), BinaryOp::Subtract, num(2.0)),
+            BinaryOp::Subtract,
+            num(3.0)
+        )
+    );
+    assert_eq!(
+        parse_value("8 / 4 * 2;"),
+        bin(
+            bin(num(8.0), BinaryOp::Divide, num(4.0)),
+            BinaryOp::Multiply,
+            num(2.0)
+        )
+    );
+    assert_eq!(
+        parse_value("(1 + 2) * 3;"),
+        bin(
+            bin(num(1.0), BinaryOp::Add, num(2.0)),
+            BinaryOp::Multiply,
+            num(3.0)
+        )
+    );
+    assert_eq!(
+        parse_value("1 + 1 == 2 | 3 & 4;"),
+        bin(
+            bin(num(1.0), BinaryOp::Add, num(1.0)),
+            BinaryOp::Equal,
+            bin(
+                num(2.0),
+                BinaryOp::BitOr,
+                bin(num(3.0), BinaryOp::BitAnd, num(4.0))
+            )
+        )
+    );
+}
+
+#[test]
+fn parser_from_hand_built_tokens() {
+    use simple_script_compiler::lexer::{Literal, Token};
+
+    let tokens = vec![
+        Token::Var,
+        Token::Identifier("a".to_string()),
+        Token::Equals,
+        Token::Literal(Literal::Number(1.0)),
+        Token::SemiColon,
+    ];
+    let program = Parser::from_tokens(tokens).parse().expect("should parse");
+    let expected = Parser::new(Lexer::new("var a = 1;"))
+        .parse()
+        .expect("should parse");
+    assert_eq!(program, expected);
+
+    let missing_semicolon = vec![
+        Token::Var,
+        Token::Identifier("a".to_string()),
+        Token::Equals,
+        Token::Literal(Literal::Number(1.0)),
+        Token::Identifier("a".to_string()),
+    ];
+    assert!(Parser::from_tokens(missing_semicolon).parse().is_err());
+}
+
+#[test]
+fn parser_allows_omitting_the_last_semicolon() {
+    for (without, with) in [
+        ("var x = 1", "var x = 1;"),
+        ("a, b = 1, 2", "a, b = 1, 2;"),
+        ("x = 1; 40 + 2", "x = 1; 40 + 2;"),
+        ("if (true) { 1 }", "if (true) { 1; }"),
+        ("while (a) { a = false }", "while (a) { a = false; }"),
+        (
+            "switch (1) { case 1: var y = 2 }",
+            "switch (1) { case 1: var y = 2; }",
+        ),
+    ] {
+        assert_eq!(
+            Parser::new(Lexer::new(without)).parse().expect(without),
+            Parser::new(Lexer::new(with)).parse().expect(with)
+        );
+    }
+
+    assert!(
+        Parser::new(Lexer::new("var x = 1 var y = 2;"))
+            .parse()
+            .is_err()
+    );
+    assert!(
+        Parser::new(Lexer::new("if (true) { 1 2 }"))
+            .parse()
+            .is_err()
+    );
+}
+
+#[test]
+fn parser_assignment_is_right_associative() {
+    let expr = parse_value("a = b = 5;");
+    match expr {
+        Expression::Assignment { name, value } => {
+            assert_eq!(name, "a");
+            match *value {
+                Expression::Assignment {
+                    ref name,
+                    ref value,
+                } => {
+                    assert_eq!(name, "b");
+                    assert_eq!(**value, num(5.0));
+                }
+                ref other => panic!("expected nested assignment, got {:?}", other),
+            }
+        }
+        other => panic!("expected assignment, got {:?}", other),
+    }
+
+    assert!(Parser::new(Lexer::new("1 + 2 = 3;")).parse().is_err());
+}
+
+#[test]
+fn parse_single_expression_rejects_trailing_tokens() {
+    use simple_script_compiler::lexer::{Literal, Token};
+    use simple_script_compiler::parser::ParseError;
+
+    let expr = Parser::new(Lexer::new("2 + 3"))
+        .parse_single_expression()
+        .expect("should parse");
+    assert!(matches!(
+        expr,
+        Expression::Binary {
+            op: BinaryOp::Add,
+            ..
+        }
+    ));
+
+    match Parser::new(Lexer::new("2 + 3 4")).parse_single_expression() {
+        Err(ParseError::UnexpectedToken { found, .. }) => {
+            assert_eq!(found, Token::Literal(Literal::Number(4.0)))
+        }
+        other => panic!("expected UnexpectedToken, got {:?}", other),
+    }
+}
+
+#[test]
+fn parser_rejects_unterminated_block_comment() {
+    use simple_script_compiler::parser::ParseError;
+
+    match Parser::new(Lexer::new("var x = 1; /* oops")).parse() {
+        Err(ParseError::UnexpectedToken { found, .. }) => {
+            assert!(matches!(
+                found,
+                simple_script_compiler::lexer::Token::Error(_)
+            ))
+        }
+        other => panic!("expected UnexpectedToken, got {:?}", other),
+    }
+}
+
+#[test]
+fn parser_rejects_invalid_utf8_from_reader() {
+    use simple_script_compiler::parser::ParseError;
+
+    let src: &[u8] = b"var a = 1;\nvar b = 2;\nprint(\"\xff\");\nvar c = 3;\n";
+    match Parser::new(Lexer::from_reader(src)).parse() {
+        Err(ParseError::UnexpectedToken { found, .. }) => {
+            assert!(matches!(
+                found,
+                simple_script_compiler::lexer::Token::Error(_)
+            ))
+        }
+        other => panic!("expected UnexpectedToken, got {:?}", other),
+    }
+}
+
+#[test]
+fn parser_if_else_blocks() {
+    let program = Parser::new(Lexer::new("if (a) { b = 1; c; } else { d; }"))
+        .parse()
+        .expect("should parse");
+    match &program.statements[0] {
+        Statement::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            assert_eq!(*condition, Expression::Identifier("a".to_string()));
+            assert_eq!(then_branch.len(), 2);
+            assert_eq!(else_branch.as_ref().map(Vec::len), Some(1));
+        }
+        other => panic!("expected if statement, got {:?}", other),
+    }
+
+    assert!(Parser::new(Lexer::new("if (a) b;")).parse().is_err());
+    assert!(Parser::new(Lexer::new("if (a) { b;")).parse().is_err());
+}
+
+#[test]
+fn parse_all_keeps_valid_statements_around_errors() {
+    use simple_script_compiler::parser::ParseError;
+
+    let (program, errors) =
+        Parser::new(Lexer::new("var a = 1; var = 2; var b = a; 1 + ;")).parse_all();
+
+    assert_eq!(program.len(), 2);
+    assert!(matches!(
+        &program.statements[0],
+        Statement::VarDeclaration { name, .. } if name == "a"
+    ));
+    assert!(matches!(
+        &program.statements[1],
+        Statement::VarDeclaration { name, .. } if name == "b"
+    ));
+    assert_eq!(errors.len(), 2);
+    assert!(matches!(errors[0], ParseError::UnexpectedToken { .. }));
+
+    let (program, errors) = Parser::new(Lexer::new("var a = 1;")).parse_all();
+    assert_eq!(program.len(), 1);
+    assert!(errors.is_empty());
+}
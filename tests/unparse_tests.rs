@@ -0,0 +1,47 @@
+use simple_script_compiler::lexer::Lexer;
+use simple_script_compiler::parser::{Parser, Program};
+use simple_script_compiler::unparse::unparse;
+
+fn parse(src: &str) -> Program {
+    Parser::new(Lexer::new(src)).parse().expect("should parse")
+}
+
+#[test]
+fn unparse_round_trips_sample_programs() {
+    let samples = [
+        r#"var x = 32424 * 312; println(2 * 4 + (3 + 6 + -7 * 2) / 2 + x);"#,
+        r#"var s = "Hello" + ", " + "World"; var t = typeof(s);"#,
+        "var a = 1 - (2 - 3); var b = (1 - 2) - 3; var c = 8 / (4 / 2) % 3;",
+        "var mask = (1 | 2) & 3 == 2; var shifted = (1 + 1) << 2;",
+        "var a = 1; var b = 2; a, b = b, a;",
+        r#"switch (1 + 1) { case 1: println("one"); case 2: var a = 2; default: print("other"); }"#,
+        "var inf = Infinity; var neg = -Infinity; var z = -0.5 * 10;",
+        "f(); g(1, true, x); math.square(2);",
+        "var a = 0; var b = 0; a = b = 5; var c = (a = 1) + 2;",
+        "if (a == 1) { b = 2; } else { if (c) { d(); } } if (e) { f(); }",
+        "var i = 0; while (i != 3) { i = i + 1; if (i == 2) { print(i); } }",
+        r#"var q = "say \"hi\"\n\t\\"; var r = 'it\'s';"#,
+    ];
+
+    for src in samples {
+        let program = parse(src);
+        let printed = unparse(&program);
+        assert_eq!(
+            parse(&printed),
+            program,
+            "round trip of {src:?} via {printed:?}"
+        );
+    }
+}
+
+#[test]
+fn unparse_uses_minimal_parentheses() {
+    let printed = unparse(&parse("var v = ((1 + 2)) * 3 + (4 * 5);"));
+    assert_eq!(printed, "var v = (1 + 2) * 3 + 4 * 5;\n");
+
+    let printed = unparse(&parse(r#"switch (x) { case 1: y; default: z; }"#));
+    assert_eq!(
+        printed,
+        "switch (x) {\n    case 1:\n        y;\n    default:\n        z;\n}\n"
+    );
+}